@@ -0,0 +1,62 @@
+//! Move generation: `State::actions` on a few representative positions and
+//! `Position::advance` from every square.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use kariuchi_solver::{
+    game::State,
+    position::{Position, POSITIONS},
+    sticks::Sticks,
+};
+
+fn positions() -> Vec<(&'static str, State)> {
+    let r = Position::route;
+    let k = Position::shortcut;
+    let (s, g, c) = (Position::start(), Position::goal(), Position::center());
+    vec![
+        ("start", State::new()),
+        // Stacks on the route and a piece at a shortcut entrance.
+        (
+            "route",
+            State::from_pieces([r(5), r(5), r(8), s], [r(3), r(9), s, s], true),
+        ),
+        // The center and the shortcuts, where one throw leads several ways.
+        (
+            "shortcuts",
+            State::from_pieces([c, k(1, 1), r(10), g], [s, r(6), c, k(4, 0)], true),
+        ),
+        // Close to the end.
+        (
+            "endgame",
+            State::from_pieces([g, g, r(27), r(29)], [g, r(26), r(28), k(5, 2)], true),
+        ),
+    ]
+}
+
+fn actions(c: &mut Criterion) {
+    let mut g = c.benchmark_group("actions");
+    for (name, state) in positions() {
+        g.bench_function(name, |b| {
+            b.iter(|| {
+                for (_, sticks) in Sticks::all_sticks() {
+                    black_box(state.actions(sticks));
+                }
+            })
+        });
+    }
+    g.finish();
+}
+
+fn advance(c: &mut Criterion) {
+    c.bench_function("advance", |b| {
+        b.iter(|| {
+            for i in 0..POSITIONS {
+                for d in 1..=5 {
+                    black_box(Position::from(i).advance(d));
+                }
+            }
+        })
+    });
+}
+
+criterion_group!(benches, actions, advance);
+criterion_main!(benches);
@@ -0,0 +1,45 @@
+//! Random playouts from the start on `State` against `Packed`, with the same throws and
+//! move choices for both, and `Evaluate::rollout_once` as the search runs it.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use kariuchi_solver::{evaluate::Evaluate, game::State, packed::Packed, sticks::Sticks};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+fn state_playout(rng: &mut StdRng) -> bool {
+    let mut s = State::new();
+    while !s.is_end() {
+        let actions = s.actions(Sticks::throw_with(rng));
+        s.act(actions[rng.gen_range(0..actions.len())]);
+    }
+    s.is_win_first()
+}
+
+fn packed_playout(rng: &mut StdRng) -> bool {
+    let mut p = Packed::from(&State::new());
+    let mut actions = Vec::with_capacity(16);
+    while !p.is_end() {
+        p.actions_into(Sticks::throw_with(rng), &mut actions);
+        p.act(actions[rng.gen_range(0..actions.len())]);
+    }
+    p.is_win_first()
+}
+
+fn rollout(c: &mut Criterion) {
+    let mut g = c.benchmark_group("rollout");
+    g.bench_function("state", |b| {
+        let mut rng = StdRng::seed_from_u64(1);
+        b.iter(|| state_playout(&mut rng))
+    });
+    g.bench_function("packed", |b| {
+        let mut rng = StdRng::seed_from_u64(1);
+        b.iter(|| packed_playout(&mut rng))
+    });
+    g.bench_function("rollout_once", |b| {
+        let mut rng = StdRng::seed_from_u64(1);
+        b.iter(|| Evaluate::rollout_once_with(State::new(), &mut rng))
+    });
+    g.finish();
+}
+
+criterion_group!(benches, rollout);
+criterion_main!(benches);
@@ -0,0 +1,38 @@
+//! `Evaluate::evaluate` at ply 1 to 3 with seeded playouts, from the start and from a
+//! middle-game position.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use kariuchi_solver::{
+    evaluate::{Evaluate, Opt},
+    game::State,
+    position::Position,
+};
+
+fn middle() -> State {
+    let r = Position::route;
+    State::from_pieces(
+        [r(3), r(3), Position::shortcut(1, 1), Position::goal()],
+        [
+            Position::start(),
+            r(6),
+            Position::center(),
+            Position::shortcut(4, 0),
+        ],
+        true,
+    )
+}
+
+fn evaluate(c: &mut Criterion) {
+    let mut g = c.benchmark_group("evaluate");
+    g.sample_size(10);
+    for (name, state) in [("start", State::new()), ("middle", middle())] {
+        for ply in 1..=3 {
+            let e = Evaluate::new(Opt { ply, rollout: 4 }, state.clone()).with_seed(1);
+            g.bench_function(format!("{}/ply{}", name, ply), |b| b.iter(|| e.evaluate()));
+        }
+    }
+    g.finish();
+}
+
+criterion_group!(benches, evaluate);
+criterion_main!(benches);
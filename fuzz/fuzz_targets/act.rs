@@ -0,0 +1,75 @@
+//! Applies byte-derived moves from the start with `State::try_act`. Every four bytes are
+//! one move `[sticks, at, to, num]`; when the high bit of `sticks` is set, `at` instead
+//! picks one of the legal moves for the throw, which lets the fuzzer reach deep games.
+//! `try_act` must accept exactly the moves `State::actions` offers while the game is on.
+//! A rejected move must leave the state as it was; an accepted one must move exactly
+//! `num` pieces and capture exactly what stood on `to`.
+#![no_main]
+
+use kariuchi_solver::{
+    game::{Action, State},
+    position::{Position, POSITIONS},
+    sticks::Sticks,
+};
+use libfuzzer_sys::fuzz_target;
+
+mod invariants;
+
+fn count(s: &State, first: bool, p: Position) -> usize {
+    s.pieces(first).iter().filter(|q| **q == p).count()
+}
+
+fn decode(s: &State, b: &[u8]) -> Option<Action> {
+    if b[0] & 0x80 != 0 {
+        if s.is_end() {
+            return None;
+        }
+        let sticks = Sticks::checked(usize::from(b[0] & 0x7f) % 5 + 1).ok()?;
+        let actions = s.actions(sticks);
+        return Some(actions[usize::from(b[1]) % actions.len()]);
+    }
+    let sticks = Sticks::checked(usize::from(b[0])).ok()?;
+    let at = Position::checked(usize::from(b[1])).ok()?;
+    let to = Position::checked(usize::from(b[2])).ok()?;
+    Action::try_new(at, to, usize::from(b[3]), sticks).ok()
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut s = State::new();
+    for b in data.chunks_exact(4) {
+        let Some(act) = decode(&s, b) else {
+            continue;
+        };
+        let before = s.clone();
+        let me = s.is_first();
+        let legal = !s.is_end() && s.actions(act.sticks()).contains(&act);
+        let accepted = s.try_act(act).is_ok();
+        assert_eq!(accepted, legal, "{} in\n{}", act, before);
+        if !accepted {
+            assert_eq!(s, before);
+            assert_eq!(s.is_first(), before.is_first());
+            continue;
+        }
+        invariants::check(&s);
+        assert_eq!(s.is_first() != me, !act.sticks().replay());
+        let captured = if act.to() == Position::goal() {
+            0
+        } else {
+            count(&before, !me, act.to())
+        };
+        for i in 0..POSITIONS {
+            let p = Position::from(i);
+            let mine = count(&before, me, p) + usize::from(p == act.to()) * act.num()
+                - usize::from(p == act.at()) * act.num();
+            assert_eq!(count(&s, me, p), mine);
+            let theirs = if p == act.to() {
+                count(&before, !me, p) - captured
+            } else if p == Position::start() {
+                count(&before, !me, p) + captured
+            } else {
+                count(&before, !me, p)
+            };
+            assert_eq!(count(&s, !me, p), theirs);
+        }
+    }
+});
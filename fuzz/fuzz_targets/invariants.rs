@@ -0,0 +1,14 @@
+use kariuchi_solver::{game::State, position::Position};
+
+// What must hold of every state, however it was reached.
+pub fn check(s: &State) {
+    let home = |first| s.pieces(first).iter().all(|p| *p == Position::goal());
+    assert_eq!(s.is_end(), home(true) || home(false), "{}", s);
+    if s.is_end() {
+        assert_eq!(s.is_win_first(), home(true), "{}", s);
+    }
+    let scratch = State::from_pieces(*s.pieces(true), *s.pieces(false), s.is_first());
+    assert_eq!(s.key(), scratch.key(), "{}", s);
+    assert_eq!(*s, scratch);
+    assert_eq!(s.to_string().parse::<State>().as_ref(), Ok(s));
+}
@@ -0,0 +1,25 @@
+//! Parses arbitrary text as a position and as a game record. Whatever parses must replay
+//! without panicking, keep the invariants and read back the same after writing it out.
+#![no_main]
+
+use kariuchi_solver::{game::State, record::Record};
+use libfuzzer_sys::fuzz_target;
+
+mod invariants;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(s) = text.parse::<State>() {
+        invariants::check(&s);
+    }
+    if let Ok(record) = Record::load(text) {
+        let states = record.states().expect("a loaded record replays");
+        assert_eq!(states.len(), record.moves.len() + 1);
+        for s in &states {
+            invariants::check(s);
+        }
+        assert_eq!(Record::load(&record.save()).as_ref(), Ok(&record));
+    }
+});
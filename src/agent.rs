@@ -0,0 +1,646 @@
+//! Players: the `Agent` trait, its human, random, greedy, expectimax and MCTS
+//! implementations, `from_spec` to build one from a command-line spec, and games between
+//! two of them.
+
+use crate::{
+    evaluate::{self, Evaluate, Opt},
+    game::{Action, State},
+    stats::{Report, Stats},
+    sticks::Sticks,
+    tape::Tape,
+    trace::Trace,
+};
+
+/// A player: picks one of the legal moves for a throw.
+pub trait Agent {
+    /// The name shown when the agent moves.
+    fn name(&self) -> String;
+    /// Picks one of `actions`, the legal moves of `state` for `sticks`; never empty.
+    fn choose(&mut self, state: &State, sticks: Sticks, actions: &[Action]) -> Action;
+    /// Explanation of the last choice (e.g. evaluated win rates), if the agent has one.
+    fn info(&self) -> Option<String> {
+        None
+    }
+    /// Whether to report search progress on the console while choosing, and log what each
+    /// search cost through `stats::Report::log`.
+    fn set_progress(&mut self, _on: bool) {}
+}
+
+/// Builds an agent from a spec like `human`, `random`, `greedy`,
+/// `expectimax:ply=3,rollout=10,tries=3` or `mcts:iterations=2000`. `expectimax:trace=1`
+/// adds the search tree of every choice to `info`; `human` takes `ply`, `rollout` and
+/// `tries` for the probabilities it shows.
+pub fn from_spec(spec: &str) -> Result<Box<dyn Agent + Send>, String> {
+    let (name, args) = match spec.split_once(':') {
+        Some((name, args)) => (name, args),
+        None => (spec, ""),
+    };
+    let mut params = vec![];
+    for kv in args.split(',').filter(|kv| !kv.is_empty()) {
+        let (k, v) = kv
+            .split_once('=')
+            .ok_or_else(|| format!("bad parameter `{}` in `{}`", kv, spec))?;
+        params.push((k, v));
+    }
+    for (k, _) in &params {
+        let known: &[&str] = match name {
+            "human" => &["ply", "rollout", "tries"],
+            "expectimax" => &["ply", "rollout", "tries", "trace"],
+            "mcts" => &["iterations", "exploration"],
+            _ => &[],
+        };
+        if !known.contains(k) {
+            return Err(format!("unknown parameter `{}` for `{}`", k, name));
+        }
+    }
+    let get = |key: &str| params.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+    let int = |key: &str, default: usize| -> Result<usize, String> {
+        get(key).map_or(Ok(default), |v| {
+            v.parse()
+                .map_err(|e| format!("bad value for `{}`: {}", key, e))
+        })
+    };
+    // Budgets of zero playouts or tries would average over nothing.
+    let positive = |key: &str, default: usize| -> Result<usize, String> {
+        match int(key, default)? {
+            0 => Err(format!("`{}` must be positive", key)),
+            n => Ok(n),
+        }
+    };
+    let agent: Box<dyn Agent + Send> = match name {
+        "human" => {
+            let opt = Opt {
+                ply: int("ply", 3)?,
+                rollout: positive("rollout", 10)?,
+            };
+            Box::new(Human::new(opt, positive("tries", 3)?))
+        }
+        "random" => Box::new(Random),
+        "greedy" => Box::new(Greedy::default()),
+        "expectimax" => {
+            let opt = Opt {
+                ply: int("ply", 3)?,
+                rollout: positive("rollout", 10)?,
+            };
+            let mut agent = Expectimax::new(opt, positive("tries", 3)?);
+            agent.trace = int("trace", 0)? != 0;
+            Box::new(agent)
+        }
+        "mcts" => {
+            let exploration = match get("exploration") {
+                None => 1.4,
+                Some(v) => v
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|c| c.is_finite() && *c >= 0.)
+                    .ok_or_else(|| format!("bad value for `exploration`: {}", v))?,
+            };
+            Box::new(Mcts::new(positive("iterations", 2000)?, exploration))
+        }
+        _ => return Err(format!("unknown agent `{}`", name)),
+    };
+    Ok(agent)
+}
+
+/// `from_spec` for agents that play without a console: refuses `human`, which would block
+/// reading stdin.
+pub fn engine_from_spec(spec: &str) -> Result<Box<dyn Agent + Send>, String> {
+    if spec.split(':').next() == Some("human") {
+        return Err(format!("`{}` is not an engine", spec));
+    }
+    from_spec(spec)
+}
+
+/// Plays a game from `state` to the end without printing anything, drawing throws from `tape`.
+/// Stops early if a fixed tape runs out.
+pub fn play(
+    mut state: State,
+    first: &mut dyn Agent,
+    second: &mut dyn Agent,
+    tape: &mut Tape,
+) -> State {
+    while !state.is_end() {
+        let Some(sticks) = tape.throw() else {
+            break;
+        };
+        let actions = state.actions(sticks);
+        let act = if state.is_first() {
+            first.choose(&state, sticks, &actions)
+        } else {
+            second.choose(&state, sticks, &actions)
+        };
+        state.act(act);
+    }
+    state
+}
+
+/// Reads moves from stdin after showing the win probability of each one, searched like
+/// `Expectimax` does with `tries` tries.
+pub struct Human {
+    opt: Opt,
+    tries: usize,
+    progress: bool,
+}
+
+impl Human {
+    /// A human shown the win probabilities of a search with `opt` and `tries` tries.
+    pub fn new(opt: Opt, tries: usize) -> Self {
+        Human {
+            opt,
+            tries,
+            progress: false,
+        }
+    }
+}
+
+impl Default for Human {
+    fn default() -> Self {
+        Human::new(
+            Opt {
+                ply: 3,
+                rollout: 10,
+            },
+            3,
+        )
+    }
+}
+
+impl Agent for Human {
+    fn name(&self) -> String {
+        "Player".to_string()
+    }
+    fn choose(&mut self, state: &State, _sticks: Sticks, actions: &[Action]) -> Action {
+        let p = evaluate_par(state.clone(), actions, self.opt, self.tries, self.progress);
+        for (i, (act, p)) in actions.iter().zip(p).enumerate() {
+            let p: Vec<String> = p.iter().map(|p| format!("{:0.4}", p)).collect();
+            println!(
+                "- {} : {: <24} [{}]",
+                i,
+                state.describe(*act).to_string(),
+                p.join(", ")
+            );
+        }
+        actions[read_int(actions.len())]
+    }
+    fn set_progress(&mut self, on: bool) {
+        self.progress = on;
+    }
+}
+
+fn read_int(max: usize) -> usize {
+    loop {
+        print!("> ");
+        use std::io::Write;
+        if let Err(e) = std::io::stdout().flush() {
+            println!("Error: {}", e);
+            continue;
+        }
+        let mut line = String::new();
+        if let Err(e) = std::io::stdin().read_line(&mut line) {
+            println!("Error: {}", e);
+            continue;
+        }
+        if line.trim() == "" && max == 1 {
+            println!("0 (forced)");
+            return 0;
+        }
+        let num = match line.trim().parse::<usize>() {
+            Ok(n) => n,
+            Err(e) => {
+                println!("Error: {}", e);
+                continue;
+            }
+        };
+        if num >= max {
+            println!("Error: out of range");
+            continue;
+        }
+        return num;
+    }
+}
+
+/// Picks a legal move uniformly at random.
+pub struct Random;
+
+impl Agent for Random {
+    fn name(&self) -> String {
+        "Random".to_string()
+    }
+    fn choose(&mut self, _state: &State, _sticks: Sticks, actions: &[Action]) -> Action {
+        actions[rand::random::<usize>() % actions.len()]
+    }
+}
+
+/// Picks the move that leaves the best `evaluate::progress` for the mover.
+#[derive(Default)]
+pub struct Greedy {
+    scores: Vec<(Action, isize)>,
+}
+
+impl Agent for Greedy {
+    fn name(&self) -> String {
+        "Greedy".to_string()
+    }
+    fn choose(&mut self, state: &State, _sticks: Sticks, actions: &[Action]) -> Action {
+        let first = state.is_first();
+        self.scores = actions
+            .iter()
+            .map(|act| {
+                let mut s = state.clone();
+                s.act(*act);
+                (*act, evaluate::progress(&s, first))
+            })
+            .collect();
+        let mut best = 0;
+        for i in 1..self.scores.len() {
+            if self.scores[i].1 > self.scores[best].1 {
+                best = i;
+            }
+        }
+        actions[best]
+    }
+    fn info(&self) -> Option<String> {
+        let lines: Vec<String> = self
+            .scores
+            .iter()
+            .enumerate()
+            .map(|(i, (act, v))| format!("- {} : {: <15} {}", i, format!("{}", act), v))
+            .collect();
+        Some(lines.join("\n"))
+    }
+}
+
+/// Expectimax search with random rollouts at the leaves, repeated `tries` times per move.
+pub struct Expectimax {
+    opt: Opt,
+    tries: usize,
+    progress: bool,
+    last: Vec<(Action, Vec<f64>)>,
+    /// Whether to search with `Evaluate::trace_tries` instead, keeping the tree of each
+    /// choice to show it; the move played is the one the trace ranks first.
+    pub trace: bool,
+    last_trace: Option<Trace>,
+}
+
+impl Expectimax {
+    /// Searches each move with `opt`, `tries` times, and plays the best mean.
+    pub fn new(opt: Opt, tries: usize) -> Self {
+        Expectimax {
+            opt,
+            tries,
+            progress: false,
+            last: vec![],
+            trace: false,
+            last_trace: None,
+        }
+    }
+}
+
+impl Agent for Expectimax {
+    fn name(&self) -> String {
+        "CPU".to_string()
+    }
+    fn choose(&mut self, state: &State, sticks: Sticks, actions: &[Action]) -> Action {
+        if self.trace {
+            let trace = Evaluate::new(self.opt, state.clone()).trace_tries(sticks, self.tries);
+            self.last = actions
+                .iter()
+                .map(|act| {
+                    let v = trace.alternatives.iter().filter(|(a, _)| a == act);
+                    (*act, v.map(|(_, v)| *v).collect())
+                })
+                .collect();
+            let act = trace.best().unwrap_or(actions[0]);
+            self.last_trace = Some(trace);
+            return act;
+        }
+        let (p, report) =
+            evaluate_par_stats(state.clone(), actions, self.opt, self.tries, self.progress);
+        // Games without a console, like the arena's, would only flood the log.
+        if self.progress {
+            report.log(&self.name());
+        }
+        let i = p
+            .iter()
+            .map(|a| a.iter().sum::<f64>())
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+            .unwrap();
+        self.last = actions.iter().copied().zip(p).collect();
+        actions[i]
+    }
+    fn info(&self) -> Option<String> {
+        let lines: Vec<String> = self
+            .last
+            .iter()
+            .enumerate()
+            .map(|(i, (act, p))| {
+                let p: Vec<String> = p.iter().map(|p| format!("{:0.4}", p)).collect();
+                format!("- {} : {: <15} [{}]", i, format!("{}", act), p.join(", "))
+            })
+            .collect();
+        let mut info = lines.join("\n");
+        if let Some(trace) = &self.last_trace {
+            info += &format!("\n{}", trace.to_string().trim_end());
+        }
+        Some(info)
+    }
+    fn set_progress(&mut self, on: bool) {
+        self.progress = on;
+    }
+}
+
+/// The win probability for the mover of each of `actions` played in `state`, searched
+/// `tries` times each with `opt` in parallel. With `progress`, counts finished searches on
+/// the console.
+pub fn evaluate_par(
+    state: State,
+    actions: &[Action],
+    opt: Opt,
+    tries: usize,
+    progress: bool,
+) -> Vec<Vec<f64>> {
+    evaluate_par_stats(state, actions, opt, tries, progress).0
+}
+
+/// `evaluate_par` with a `Report` of what the search cost.
+pub fn evaluate_par_stats(
+    state: State,
+    actions: &[Action],
+    opt: Opt,
+    tries: usize,
+    progress: bool,
+) -> (Vec<Vec<f64>>, Report) {
+    use rayon::prelude::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    let start = Instant::now();
+    let stats = Arc::new(Stats::new(opt.ply));
+    let solved = std::sync::Arc::<Mutex<usize>>::new(Mutex::new(0));
+    let max = actions.len() * tries;
+    if progress {
+        print!("{} / {}", solved.lock().unwrap(), max);
+        std::io::stdout().flush().unwrap();
+    }
+
+    let res: Vec<(Vec<f64>, Duration)> = actions
+        .par_iter()
+        .map(|act| {
+            let time = Mutex::new(Duration::ZERO);
+            let values = (0..tries)
+                .into_par_iter()
+                .map(|_| {
+                    let begin = Instant::now();
+                    let mut st = state.clone();
+                    st.act(*act);
+                    let p = Evaluate::new(opt, st.clone())
+                        .with_stats(stats.clone())
+                        .evaluate();
+                    *time.lock().unwrap() += begin.elapsed();
+                    // The evaluation is from the side to move after `act`, which is still the
+                    // mover after a replay throw.
+                    let p = if st.is_first() == state.is_first() {
+                        p
+                    } else {
+                        1. - p
+                    };
+                    let mut solved = solved.lock().unwrap();
+                    *solved += 1;
+                    if progress {
+                        print!("\r{} / {}", solved, max);
+                        std::io::stdout().flush().unwrap();
+                    }
+                    p
+                })
+                .collect();
+            (values, time.into_inner().unwrap())
+        })
+        .collect();
+    if progress {
+        print!("\r");
+    }
+    let times = actions.iter().zip(&res).map(|(a, r)| (*a, r.1)).collect();
+    let report = Report::new(&stats, times, start.elapsed());
+    (res.into_iter().map(|r| r.0).collect(), report)
+}
+
+/// Monte Carlo tree search over decision nodes; throws are sampled on the way down.
+pub struct Mcts {
+    iterations: usize,
+    exploration: f64,
+    last: Vec<(Action, usize, f64)>,
+}
+
+#[derive(Default)]
+struct Node {
+    visits: usize,
+    // Sum of results from the first player's point of view.
+    first_wins: f64,
+    children: Vec<(Sticks, Vec<(Action, Node)>)>,
+}
+
+impl Mcts {
+    /// A search of `iterations` iterations with `exploration` as the UCB1 exploration constant.
+    pub fn new(iterations: usize, exploration: f64) -> Self {
+        Mcts {
+            iterations,
+            exploration,
+            last: vec![],
+        }
+    }
+    fn select(&self, children: &[(Action, Node)], first: bool) -> usize {
+        let total: usize = children.iter().map(|(_, n)| n.visits).sum();
+        let mut best = 0;
+        let mut best_score = f64::NEG_INFINITY;
+        for (i, (_, n)) in children.iter().enumerate() {
+            if n.visits == 0 {
+                return i;
+            }
+            let p = n.first_wins / n.visits as f64;
+            let p = if first { p } else { 1. - p };
+            let score = p + self.exploration * ((total as f64).ln() / n.visits as f64).sqrt();
+            if score > best_score {
+                best = i;
+                best_score = score;
+            }
+        }
+        best
+    }
+    fn descend(&self, node: &mut Node, state: &mut State) -> f64 {
+        let v = if state.is_end() {
+            if state.is_win_first() {
+                1.
+            } else {
+                0.
+            }
+        } else if node.visits == 0 {
+            let p = Evaluate::rollout_once(state.clone());
+            if state.is_first() {
+                p
+            } else {
+                1. - p
+            }
+        } else {
+            let sticks = Sticks::throw();
+            let first = state.is_first();
+            let idx = match node.children.iter().position(|(s, _)| *s == sticks) {
+                Some(i) => i,
+                None => {
+                    let actions = state.actions(sticks);
+                    let children = actions.into_iter().map(|a| (a, Node::default()));
+                    node.children.push((sticks, children.collect()));
+                    node.children.len() - 1
+                }
+            };
+            let children = &mut node.children[idx].1;
+            let k = self.select(children, first);
+            state.act(children[k].0);
+            self.descend(&mut children[k].1, state)
+        };
+        node.visits += 1;
+        node.first_wins += v;
+        v
+    }
+}
+
+impl Agent for Mcts {
+    fn name(&self) -> String {
+        "MCTS".to_string()
+    }
+    fn choose(&mut self, state: &State, _sticks: Sticks, actions: &[Action]) -> Action {
+        let first = state.is_first();
+        let mut root: Vec<(Action, Node)> = actions.iter().map(|a| (*a, Node::default())).collect();
+        for _ in 0..self.iterations {
+            let k = self.select(&root, first);
+            let mut s = state.clone();
+            s.act(root[k].0);
+            self.descend(&mut root[k].1, &mut s);
+        }
+        self.last = root
+            .iter()
+            .map(|(a, n)| {
+                let p = n.first_wins / n.visits.max(1) as f64;
+                (*a, n.visits, if first { p } else { 1. - p })
+            })
+            .collect();
+        let (best, _) = root
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (_, n))| n.visits)
+            .unwrap();
+        actions[best]
+    }
+    fn info(&self) -> Option<String> {
+        let lines: Vec<String> = self
+            .last
+            .iter()
+            .enumerate()
+            .map(|(i, (act, n, p))| {
+                format!("- {} : {: <15} {:0.4} ({})", i, format!("{}", act), p, n)
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn spec() {
+        assert_eq!(from_spec("random").unwrap().name(), "Random");
+        assert_eq!(
+            from_spec("expectimax:ply=1,rollout=2").unwrap().name(),
+            "CPU"
+        );
+        assert!(from_spec("expectimax:depth=1").is_err());
+        for bad in [
+            "expectimax:rollout=0",
+            "expectimax:tries=0",
+            "expectimax:rollout=0.5",
+            "expectimax:ply=-1",
+            "mcts:iterations=0",
+            "mcts:exploration=nan",
+        ] {
+            let e = from_spec(bad).err().unwrap();
+            println!("{}: {}", bad, e);
+        }
+        assert!(from_spec("expectimax:ply=0,rollout=1,tries=1,trace=1").is_ok());
+        assert!(from_spec("alphabeta").is_err());
+    }
+    #[test]
+    fn play_random() {
+        let mut tape = Tape::random();
+        let s = play(State::new(), &mut Random, &mut Greedy::default(), &mut tape);
+        println!("{}", s);
+        assert!(s.is_end());
+    }
+    #[test]
+    fn replay_values_for_mover() {
+        // A 4 from R29 wins at once and keeps the turn; from R20 it only keeps the turn.
+        // Either way the value must be the mover's, not the opponent's.
+        use crate::position::Position;
+        let (g, s) = (Position::goal(), Position::start());
+        let opt = Opt {
+            ply: 0,
+            rollout: 20,
+        };
+        let sticks = Sticks::from(4);
+        let win = State::from_pieces([g, g, g, Position::route(29)], [s; 4], true);
+        let actions = win.actions(sticks);
+        let p = evaluate_par(win.clone(), &actions, opt, 1, false);
+        assert_eq!(p[0][0], 1.);
+        let ahead = State::from_pieces([g, g, g, Position::route(20)], [s; 4], true);
+        let actions = ahead.actions(sticks);
+        let p = evaluate_par(ahead.clone(), &actions, opt, 2, false);
+        println!("{:?}", p);
+        assert!(p[0].iter().all(|p| *p > 0.5));
+    }
+    #[test]
+    fn greedy_hits() {
+        let mut s = State::new();
+        s.act(s.actions(Sticks::from(3))[0]);
+        let sticks = Sticks::from(3);
+        let actions = s.actions(sticks);
+        let act = Greedy::default().choose(&s, sticks, &actions);
+        s.act(act);
+        assert_eq!(s.pieces(true)[0], crate::position::Position::start());
+    }
+    #[test]
+    fn mcts_choose() {
+        let s = State::new();
+        let sticks = Sticks::from(2);
+        let actions = s.actions(sticks);
+        let mut m = Mcts::new(200, 1.4);
+        let act = m.choose(&s, sticks, &actions);
+        println!("{}\n{}", act, m.info().unwrap());
+    }
+    #[test]
+    fn trace_marks_choice() {
+        // After a 4 the first side may enter a new piece or move the one on R4.
+        let mut s = State::new();
+        s.act(s.actions(Sticks::from(4))[0]);
+        let sticks = Sticks::from(2);
+        let actions = s.actions(sticks);
+        let mut e = Expectimax::new(Opt { ply: 1, rollout: 2 }, 3);
+        e.trace = true;
+        for _ in 0..5 {
+            let act = e.choose(&s, sticks, &actions);
+            let trace = e.last_trace.as_ref().unwrap();
+            println!("{}", trace);
+            assert_eq!(trace.best(), Some(act));
+            let (_, p) = e.last.iter().find(|(a, _)| *a == act).unwrap();
+            assert_eq!(trace.alternatives[0].1, p[0]);
+            assert_eq!(trace.nodes[0], 3);
+            // Ties go to the last move, as without a trace.
+            let last = e
+                .last
+                .iter()
+                .max_by(|a, b| a.1[0].total_cmp(&b.1[0]))
+                .unwrap();
+            assert_eq!(last.0, act);
+        }
+    }
+}
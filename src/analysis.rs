@@ -0,0 +1,419 @@
+//! Game review: every move of a `Record` compared with the best one the search finds, with
+//! inaccuracies and blunders marked and summed up per side.
+
+use std::fmt::Write;
+
+use crate::{
+    agent, evaluate,
+    game::{Action, State},
+    record::Record,
+};
+
+/// Review settings. Losses are in win probability of the side that moved.
+///
+/// By default every alternative gets ply 3 with 50 playouts per leaf, averaged over 3
+/// tries: five times the playouts of the default `expectimax` engine (ply 3, 10 × 3).
+#[derive(Debug, Clone, Copy)]
+pub struct Opt {
+    /// Search for every alternative.
+    pub eval: evaluate::Opt,
+    /// Searches averaged per alternative.
+    pub tries: usize,
+    /// Smallest loss marked as an inaccuracy.
+    pub inaccuracy: f64,
+    /// Smallest loss marked as a blunder.
+    pub blunder: f64,
+}
+
+impl Default for Opt {
+    fn default() -> Self {
+        Opt {
+            eval: evaluate::Opt {
+                ply: 3,
+                rollout: 50,
+            },
+            tries: 3,
+            inaccuracy: 0.02,
+            blunder: 0.05,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
+/// How bad a move was, by how much win probability it gave away.
+pub enum Mark {
+    /// Lost at least `Opt::inaccuracy`.
+    Inaccuracy,
+    /// Lost at least `Opt::blunder`.
+    Blunder,
+}
+
+/// One played move against the best alternative for the same throw.
+#[derive(Debug, Clone)]
+pub struct MoveReview {
+    /// The position the move was played in.
+    pub state: State,
+    /// The move played.
+    pub action: Action,
+    /// The move the search prefers for the same throw.
+    pub best: Action,
+    /// Win probability of the mover after the played move.
+    pub value: f64,
+    /// Win probability of the mover after the best move.
+    pub best_value: f64,
+    /// `None` for a move that lost less than an inaccuracy.
+    pub mark: Option<Mark>,
+}
+
+impl MoveReview {
+    /// Whether the first side played the move.
+    pub fn first(&self) -> bool {
+        self.state.is_first()
+    }
+    /// Win probability given away against the best move; never negative.
+    pub fn loss(&self) -> f64 {
+        (self.best_value - self.value).max(0.)
+    }
+}
+
+/// Per-side totals of a review.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Summary {
+    /// Moves the side played.
+    pub moves: usize,
+    /// Sum of `MoveReview::loss` over those moves.
+    pub total_loss: f64,
+    /// Moves marked as inaccuracies.
+    pub inaccuracies: usize,
+    /// Moves marked as blunders.
+    pub blunders: usize,
+}
+
+impl Summary {
+    /// Loss per move; 0 without moves.
+    pub fn average_loss(&self) -> f64 {
+        if self.moves == 0 {
+            0.
+        } else {
+            self.total_loss / self.moves as f64
+        }
+    }
+}
+
+/// The review of a whole game, as `analyze` returns it.
+#[derive(Debug, Clone)]
+pub struct Review {
+    /// Every move of the game, in order.
+    pub moves: Vec<MoveReview>,
+}
+
+/// Searches every alternative of every move in `record` with `opt` and compares the
+/// played move with the best one.
+pub fn analyze(record: &Record, opt: &Opt) -> Result<Review, String> {
+    let states = record.states().map_err(|e| e.to_string())?;
+    let mut moves = vec![];
+    for (state, act) in states.iter().zip(&record.moves) {
+        let actions = state.actions(act.sticks());
+        let values: Vec<f64> =
+            agent::evaluate_par(state.clone(), &actions, opt.eval, opt.tries, false)
+                .iter()
+                .map(|v| v.iter().sum::<f64>() / v.len() as f64)
+                .collect();
+        let (best, best_value) = actions
+            .iter()
+            .zip(&values)
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(a, v)| (*a, *v))
+            .unwrap();
+        let played = actions.iter().position(|a| a == act).unwrap();
+        let mut review = MoveReview {
+            state: state.clone(),
+            action: *act,
+            best,
+            value: values[played],
+            best_value,
+            mark: None,
+        };
+        if review.loss() >= opt.blunder {
+            review.mark = Some(Mark::Blunder);
+        } else if review.loss() >= opt.inaccuracy {
+            review.mark = Some(Mark::Inaccuracy);
+        }
+        moves.push(review);
+    }
+    Ok(Review { moves })
+}
+
+fn side(first: bool) -> &'static str {
+    if first {
+        "First"
+    } else {
+        "Second"
+    }
+}
+
+impl Review {
+    /// Totals over the moves of the first side, or of the second.
+    pub fn summary(&self, first: bool) -> Summary {
+        let mut s = Summary::default();
+        for m in self.moves.iter().filter(|m| m.first() == first) {
+            s.moves += 1;
+            s.total_loss += m.loss();
+            match m.mark {
+                Some(Mark::Inaccuracy) => s.inaccuracies += 1,
+                Some(Mark::Blunder) => s.blunders += 1,
+                None => {}
+            }
+        }
+        s
+    }
+    /// One line per move, `?!` marking inaccuracies and `??` blunders, then the summary.
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for (i, m) in self.moves.iter().enumerate() {
+            let mark = match m.mark {
+                None => "",
+                Some(Mark::Inaccuracy) => "?!",
+                Some(Mark::Blunder) => "??",
+            };
+            let played = format!("{}{}", m.state.describe(m.action), mark);
+            let _ = write!(
+                out,
+                "{:>4}. {:<6} {} {:<36} {:.3}",
+                i + 1,
+                side(m.first()),
+                usize::from(m.action.sticks()),
+                played,
+                m.value
+            );
+            if m.loss() > 0. {
+                let _ = write!(
+                    out,
+                    "  -{:.3}  best {} {:.3}",
+                    m.loss(),
+                    m.state.describe(m.best),
+                    m.best_value
+                );
+            }
+            out += "\n";
+        }
+        let _ = writeln!(
+            out,
+            "\n{:<8}{:>6}{:>10}{:>14}{:>10}",
+            "", "moves", "avg loss", "inaccuracies", "blunders"
+        );
+        for first in [true, false] {
+            let s = self.summary(first);
+            let _ = writeln!(
+                out,
+                "{:<8}{:>6}{:>10.4}{:>14}{:>10}",
+                side(first),
+                s.moves,
+                s.average_loss(),
+                s.inaccuracies,
+                s.blunders
+            );
+        }
+        out
+    }
+    /// The moves and the per-side summaries as one JSON object.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        let moves = self
+            .moves
+            .iter()
+            .map(|m| MoveJson {
+                side: side(m.first()).to_lowercase(),
+                sticks: usize::from(m.action.sticks()),
+                played: m.state.describe(m.action).to_string(),
+                value: m.value,
+                best: m.state.describe(m.best).to_string(),
+                best_value: m.best_value,
+                loss: m.loss(),
+                mark: m.mark,
+            })
+            .collect();
+        let summary = |first| {
+            let s = self.summary(first);
+            SummaryJson {
+                moves: s.moves,
+                average_loss: s.average_loss(),
+                inaccuracies: s.inaccuracies,
+                blunders: s.blunders,
+            }
+        };
+        let review = ReviewJson {
+            moves,
+            summary: SidesJson {
+                first: summary(true),
+                second: summary(false),
+            },
+        };
+        serde_json::to_string(&review).expect("review serializes") + "\n"
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct MoveJson {
+    side: String,
+    sticks: usize,
+    played: String,
+    value: f64,
+    best: String,
+    best_value: f64,
+    loss: f64,
+    mark: Option<Mark>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct SummaryJson {
+    moves: usize,
+    average_loss: f64,
+    inaccuracies: usize,
+    blunders: usize,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct SidesJson {
+    first: SummaryJson,
+    second: SummaryJson,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct ReviewJson {
+    moves: Vec<MoveJson>,
+    summary: SidesJson,
+}
+
+const USAGE: &str = "usage: kariuchi-solver analyze [--ply N] [--rollout N] [--tries N] [--inaccuracy P] [--blunder P] [--json] GAME_FILE";
+
+/// The `analyze` subcommand: reviews the game in GAME_FILE as text or, with `--json`, as
+/// JSON.
+pub fn main(args: &[String]) -> Result<(), String> {
+    let mut opt = Opt::default();
+    let mut json = false;
+    let mut input = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--ply" => opt.eval.ply = value()?.parse().map_err(|e| format!("--ply: {}", e))?,
+            "--rollout" => {
+                opt.eval.rollout = value()?.parse().map_err(|e| format!("--rollout: {}", e))?
+            }
+            "--tries" => opt.tries = value()?.parse().map_err(|e| format!("--tries: {}", e))?,
+            "--inaccuracy" => {
+                opt.inaccuracy = value()?
+                    .parse()
+                    .map_err(|e| format!("--inaccuracy: {}", e))?
+            }
+            "--blunder" => {
+                opt.blunder = value()?.parse().map_err(|e| format!("--blunder: {}", e))?
+            }
+            "--json" => json = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}\n{}", arg, USAGE)),
+            _ => input = Some(arg.clone()),
+        }
+    }
+    if opt.eval.rollout == 0 || opt.tries == 0 {
+        return Err("--rollout and --tries must be positive".to_string());
+    }
+    let path = input.ok_or(USAGE)?;
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+    let record = Record::load(&text).map_err(|e| format!("{}: {}", path, e))?;
+    let review = analyze(&record, &opt)?;
+    if json {
+        #[cfg(feature = "serde")]
+        print!("{}", review.to_json());
+        #[cfg(not(feature = "serde"))]
+        return Err("--json needs the `serde` feature".to_string());
+    } else {
+        print!("{}", review.to_text());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{position::Position, sticks::Sticks, tape::Tape};
+    fn fast() -> Opt {
+        Opt {
+            eval: evaluate::Opt {
+                ply: 1,
+                rollout: 10,
+            },
+            ..Opt::default()
+        }
+    }
+    #[test]
+    fn blunder() {
+        // Moving both pieces home wins at once; moving one lets the opponent win first.
+        let g = Position::goal();
+        let r29 = Position::route(29);
+        let start = State::from_pieces([g, g, r29, r29], [g, g, g, r29], true);
+        let mut record = Record::new(start.clone());
+        let slow = *start
+            .actions(Sticks::from(2))
+            .iter()
+            .find(|a| a.num() == 1)
+            .unwrap();
+        record.push(slow);
+        let review = analyze(&record, &fast()).unwrap();
+        print!("{}", review.to_text());
+        let m = &review.moves[0];
+        assert_eq!(m.best.num(), 2);
+        assert_eq!(m.best_value, 1.);
+        assert_eq!(m.mark, Some(Mark::Blunder));
+        assert_eq!(review.summary(true).blunders, 1);
+        assert_eq!(review.summary(false), Summary::default());
+    }
+    #[test]
+    fn game() {
+        let mut tape = Tape::seeded(4);
+        let mut state = State::new();
+        let mut record = Record::new(state.clone());
+        for _ in 0..12 {
+            let actions = state.actions(tape.throw().unwrap());
+            let act = *actions.last().unwrap();
+            record.push(act);
+            state.act(act);
+        }
+        let review = analyze(&record, &fast()).unwrap();
+        print!("{}", review.to_text());
+        assert_eq!(review.moves.len(), 12);
+        let (a, b) = (review.summary(true), review.summary(false));
+        assert_eq!(a.moves + b.moves, 12);
+        for m in &review.moves {
+            assert!(m.loss() >= 0.);
+            assert!(m.best_value >= m.value);
+        }
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json() {
+        let g = Position::goal();
+        let r29 = Position::route(29);
+        let start = State::from_pieces([g, g, r29, r29], [g, g, g, r29], true);
+        let mut record = Record::new(start.clone());
+        record.push(start.actions(Sticks::from(2))[0]);
+        let review = analyze(&record, &fast()).unwrap();
+        let text = review.to_json();
+        println!("{}", text);
+        let v: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(v["moves"][0]["side"], "first");
+        assert_eq!(v["moves"][0]["sticks"], 2);
+        assert_eq!(v["summary"]["second"]["moves"], 0);
+        let mark = &v["moves"][0]["mark"];
+        assert!(mark.is_null() || *mark == "blunder" || *mark == "inaccuracy");
+    }
+}
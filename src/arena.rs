@@ -0,0 +1,456 @@
+//! Engine matches: pairs of games with swapped colors on shared throws, scored in Elo and
+//! optionally stopped early by a sequential probability ratio test.
+
+use rayon::prelude::*;
+
+use crate::{agent, game::State, tape::Tape};
+
+/// A sequential probability ratio test of whether the first agent is `elo1` rather than
+/// `elo0` Elo stronger than the second.
+#[derive(Debug, Clone, Copy)]
+pub struct Sprt {
+    /// Elo difference of the null hypothesis H0.
+    pub elo0: f64,
+    /// Elo difference of the alternative hypothesis H1.
+    pub elo1: f64,
+    /// Chance of accepting H1 when H0 holds.
+    pub alpha: f64,
+    /// Chance of accepting H0 when H1 holds.
+    pub beta: f64,
+}
+
+/// Where a `Sprt` stands after the games played so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// H0 accepted: the first agent is no more than `elo0` stronger.
+    H0,
+    /// H1 accepted: the first agent is at least `elo1` stronger.
+    H1,
+    /// More games are needed.
+    Continue,
+}
+
+impl Sprt {
+    /// A test of `elo0` against `elo1` with both error rates at 5%.
+    pub fn new(elo0: f64, elo1: f64) -> Self {
+        Sprt {
+            elo0,
+            elo1,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+    /// Log-likelihood ratio of H1 (elo1) against H0 (elo0), with each game pair as one
+    /// trial: the usual normal approximation of the generalized SPRT over per-pair scores.
+    pub fn llr(&self, score: &Score) -> f64 {
+        let n = score.pair_count();
+        if n == 0 {
+            return 0.;
+        }
+        let s0 = expected(self.elo0);
+        let s1 = expected(self.elo1);
+        let var = score.pair_variance(0.5);
+        (s1 - s0) * (2. * score.rate() - s0 - s1) / (2. * var) * n as f64
+    }
+    /// The log-likelihood ratios at or beyond which `decide` accepts H0 and H1.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1. - self.alpha)).ln(),
+            ((1. - self.beta) / self.alpha).ln(),
+        )
+    }
+    /// Compares `llr` with `bounds`.
+    pub fn decide(&self, score: &Score) -> Decision {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr <= lower {
+            Decision::H0
+        } else if llr >= upper {
+            Decision::H1
+        } else {
+            Decision::Continue
+        }
+    }
+}
+
+// Expected score of a player `elo` points stronger than its opponent.
+fn expected(elo: f64) -> f64 {
+    1. / (1. + 10f64.powf(-elo / 400.))
+}
+
+fn elo(score: f64) -> f64 {
+    -400. * (1. / score - 1.).log10()
+}
+
+/// Results of a pairing, counted by game pair: `pairs[k]` pairs ended with the first agent
+/// winning `k` of the two games. The two games of a pair share their throws, so they are
+/// not independent; error bars and SPRT treat each pair as one trial. Kariuchi has no draws.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Score {
+    /// Game pairs by games the first agent won in them.
+    pub pairs: [usize; 3],
+}
+
+impl Score {
+    /// Game pairs played.
+    pub fn pair_count(&self) -> usize {
+        self.pairs.iter().sum()
+    }
+    /// Games the first agent won.
+    pub fn wins(&self) -> usize {
+        self.pairs[1] + 2 * self.pairs[2]
+    }
+    /// Games the first agent lost.
+    pub fn losses(&self) -> usize {
+        self.pairs[1] + 2 * self.pairs[0]
+    }
+    /// Games played.
+    pub fn games(&self) -> usize {
+        2 * self.pair_count()
+    }
+    /// Share of games won; 0.5 before any game.
+    pub fn rate(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.5;
+        }
+        self.wins() as f64 / self.games() as f64
+    }
+    // Variance of the per-pair score (0, 1/2 or 1), with `prior` extra pairs of each kind
+    // so that a short run of identical pairs does not look certain.
+    fn pair_variance(&self, prior: f64) -> f64 {
+        let counts = self.pairs.map(|c| c as f64 + prior);
+        let n: f64 = counts.iter().sum();
+        if n == 0. {
+            return 0.;
+        }
+        let mean = (counts[1] * 0.5 + counts[2]) / n;
+        (0..3)
+            .map(|k| counts[k] * (k as f64 / 2. - mean).powi(2))
+            .sum::<f64>()
+            / n
+    }
+    /// Half width of the 95% confidence interval of `rate`, from the spread of per-pair
+    /// scores; 0.5 before any game.
+    pub fn error(&self) -> f64 {
+        let n = self.pair_count();
+        if n == 0 {
+            return 0.5;
+        }
+        1.96 * (self.pair_variance(0.) / n as f64).sqrt()
+    }
+    /// Elo difference matching `rate`; infinite after a shutout.
+    pub fn elo(&self) -> f64 {
+        elo(self.rate())
+    }
+    /// Elo differences at both ends of the confidence interval of `rate`.
+    pub fn elo_interval(&self) -> (f64, f64) {
+        let p = self.rate();
+        let e = self.error();
+        (elo((p - e).max(0.)), elo((p + e).min(1.)))
+    }
+}
+
+/// Match settings.
+#[derive(Debug, Clone)]
+pub struct Opt {
+    /// Number of game pairs per pairing; each pair replays the same dice with colors swapped.
+    pub pairs: usize,
+    /// Seed of the throws; every pairing derives its own from it.
+    pub seed: u64,
+    /// Test that may stop a pairing before `pairs`.
+    pub sprt: Option<Sprt>,
+    /// Game pairs played between SPRT checks.
+    pub batch: usize,
+}
+
+impl Default for Opt {
+    fn default() -> Self {
+        Opt {
+            pairs: 500,
+            seed: 0,
+            sprt: None,
+            batch: 64,
+        }
+    }
+}
+
+/// The outcome of a match between two agents.
+#[derive(Debug, Clone)]
+pub struct Pairing {
+    /// Spec of the first agent, whom `score` counts for.
+    pub a: String,
+    /// Spec of the second agent.
+    pub b: String,
+    /// Results, counted by game pair.
+    pub score: Score,
+    /// Where the SPRT ended, if one ran.
+    pub decision: Option<Decision>,
+}
+
+// Plays one game with `a` moving first and one with `b` moving first over the same throws.
+// Returns how many of the two games `a` won.
+fn play_pair(a: &str, b: &str, seed: u64) -> Result<usize, String> {
+    let mut wins = 0;
+    let mut tape = Tape::seeded(seed);
+    for a_first in [true, false] {
+        let mut x = agent::engine_from_spec(a)?;
+        let mut y = agent::engine_from_spec(b)?;
+        tape.rewind();
+        let s = if a_first {
+            agent::play(State::new(), &mut *x, &mut *y, &mut tape)
+        } else {
+            agent::play(State::new(), &mut *y, &mut *x, &mut tape)
+        };
+        if s.is_win_first() == a_first {
+            wins += 1;
+        }
+    }
+    Ok(wins)
+}
+
+/// Plays game pairs between the engines `a` and `b`, batch by batch in parallel, until
+/// `opt.pairs` pairs are played or the SPRT decides. Pair `k` throws from `seed + k`.
+pub fn run_pairing(a: &str, b: &str, opt: &Opt, seed: u64) -> Result<Pairing, String> {
+    agent::engine_from_spec(a)?;
+    agent::engine_from_spec(b)?;
+    let mut score = Score::default();
+    let mut decision = opt.sprt.map(|_| Decision::Continue);
+    let mut played = 0;
+    while played < opt.pairs && decision != Some(Decision::H0) && decision != Some(Decision::H1) {
+        let n = opt.batch.max(1).min(opt.pairs - played);
+        let wins = (played..played + n)
+            .into_par_iter()
+            .map(|k| play_pair(a, b, seed.wrapping_add(k as u64)))
+            .collect::<Result<Vec<usize>, String>>()?;
+        for w in wins {
+            score.pairs[w] += 1;
+        }
+        played += n;
+        decision = opt.sprt.map(|sprt| sprt.decide(&score));
+    }
+    Ok(Pairing {
+        a: a.to_string(),
+        b: b.to_string(),
+        score,
+        decision,
+    })
+}
+
+/// Round robin over every pair of `specs`.
+pub fn run(specs: &[String], opt: &Opt) -> Result<Vec<Pairing>, String> {
+    let mut res = vec![];
+    for i in 0..specs.len() {
+        for j in i + 1..specs.len() {
+            let seed = opt.seed.wrapping_add(((i * specs.len() + j) as u64) << 32);
+            res.push(run_pairing(&specs[i], &specs[j], opt, seed)?);
+        }
+    }
+    Ok(res)
+}
+
+impl Pairing {
+    /// One line with the score, win rate and Elo with their error bars.
+    pub fn summary(&self) -> String {
+        let s = &self.score;
+        let (lo, hi) = s.elo_interval();
+        let mut line = format!(
+            "{} vs {}: {} - {} ({} games), win rate {:0.4} ± {:0.4}, elo {:+0.1} [{:+0.1}, {:+0.1}]",
+            self.a,
+            self.b,
+            s.wins(),
+            s.losses(),
+            s.games(),
+            s.rate(),
+            s.error(),
+            s.elo(),
+            lo,
+            hi
+        );
+        if let Some(d) = self.decision {
+            line += &format!(", sprt {:?}", d);
+        }
+        line
+    }
+}
+
+/// The pairings as CSV with a header line.
+pub fn to_csv(pairings: &[Pairing]) -> String {
+    let mut out = "a,b,wins,losses,games,rate,error,elo,elo_low,elo_high,sprt\n".to_string();
+    for p in pairings {
+        let s = &p.score;
+        let (lo, hi) = s.elo_interval();
+        out += &format!(
+            "\"{}\",\"{}\",{},{},{},{},{},{},{},{},{}\n",
+            p.a,
+            p.b,
+            s.wins(),
+            s.losses(),
+            s.games(),
+            s.rate(),
+            s.error(),
+            s.elo(),
+            lo,
+            hi,
+            p.decision.map(|d| format!("{:?}", d)).unwrap_or_default()
+        );
+    }
+    out
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct PairingJson<'a> {
+    a: &'a str,
+    b: &'a str,
+    wins: usize,
+    losses: usize,
+    games: usize,
+    pairs: [usize; 3],
+    rate: f64,
+    error: f64,
+    elo: f64,
+    elo_low: f64,
+    elo_high: f64,
+    sprt: Option<String>,
+}
+
+/// The pairings as a JSON array; non-finite Elo (a shutout) is written as null.
+#[cfg(feature = "serde")]
+pub fn to_json(pairings: &[Pairing]) -> String {
+    let items: Vec<PairingJson> = pairings
+        .iter()
+        .map(|p| {
+            let s = &p.score;
+            let (elo_low, elo_high) = s.elo_interval();
+            PairingJson {
+                a: &p.a,
+                b: &p.b,
+                wins: s.wins(),
+                losses: s.losses(),
+                games: s.games(),
+                pairs: s.pairs,
+                rate: s.rate(),
+                error: s.error(),
+                elo: s.elo(),
+                elo_low,
+                elo_high,
+                sprt: p.decision.map(|d| format!("{:?}", d)),
+            }
+        })
+        .collect();
+    serde_json::to_string(&items).expect("pairings serialize") + "\n"
+}
+
+const USAGE: &str = "usage: kariuchi-solver arena [--pairs N] [--seed S] [--sprt ELO0,ELO1] [--csv FILE] [--json FILE] AGENT AGENT...";
+
+/// The `arena` subcommand: a round robin between the AGENT engines.
+pub fn main(args: &[String]) -> Result<(), String> {
+    let mut opt = Opt::default();
+    let mut specs = vec![];
+    let mut csv = None;
+    let mut json = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--pairs" => opt.pairs = value()?.parse().map_err(|e| format!("--pairs: {}", e))?,
+            "--seed" => opt.seed = value()?.parse().map_err(|e| format!("--seed: {}", e))?,
+            "--sprt" => {
+                let v = value()?;
+                let (e0, e1) = v
+                    .split_once(',')
+                    .ok_or_else(|| format!("--sprt: expected ELO0,ELO1, got `{}`", v))?;
+                let e0 = e0.parse().map_err(|e| format!("--sprt: {}", e))?;
+                let e1 = e1.parse().map_err(|e| format!("--sprt: {}", e))?;
+                opt.sprt = Some(Sprt::new(e0, e1));
+            }
+            "--csv" => csv = Some(value()?.clone()),
+            "--json" => json = Some(value()?.clone()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}\n{}", arg, USAGE)),
+            _ => specs.push(arg.clone()),
+        }
+    }
+    if specs.len() < 2 {
+        return Err(USAGE.to_string());
+    }
+    let pairings = run(&specs, &opt)?;
+    for p in &pairings {
+        println!("{}", p.summary());
+    }
+    if let Some(path) = csv {
+        std::fs::write(&path, to_csv(&pairings)).map_err(|e| format!("{}: {}", path, e))?;
+    }
+    if let Some(path) = json {
+        #[cfg(feature = "serde")]
+        std::fs::write(&path, to_json(&pairings)).map_err(|e| format!("{}: {}", path, e))?;
+        #[cfg(not(feature = "serde"))]
+        return Err(format!("{}: --json needs the `serde` feature", path));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn elo_of_score() {
+        assert!(elo(0.5).abs() < 1e-9);
+        assert!((elo(expected(100.)) - 100.).abs() < 1e-9);
+        let s = Score {
+            pairs: [10, 20, 20],
+        };
+        assert_eq!((s.wins(), s.losses()), (60, 40));
+        let (lo, hi) = s.elo_interval();
+        assert!(lo < s.elo() && s.elo() < hi);
+        let none = Score::default();
+        assert_eq!(none.rate(), 0.5);
+        assert_eq!(none.error(), 0.5);
+    }
+    #[test]
+    fn pairs_are_one_trial() {
+        // Same 50% win rate over 200 games: split pairs say nothing about colour luck, while
+        // pairs won or lost together vary more than 200 independent games would.
+        let split = Score { pairs: [0, 100, 0] };
+        let together = Score { pairs: [50, 0, 50] };
+        let binomial = 1.96 * (0.25f64 / 200.).sqrt();
+        println!("{} {} {}", split.error(), together.error(), binomial);
+        assert_eq!(split.error(), 0.);
+        assert!(together.error() > binomial);
+    }
+    #[test]
+    fn sprt() {
+        let sprt = Sprt::new(0., 50.);
+        let even = Score {
+            pairs: [125, 250, 125],
+        };
+        assert_eq!(sprt.decide(&even), Decision::H0);
+        let strong = Score {
+            pairs: [100, 200, 200],
+        };
+        assert_eq!(sprt.decide(&strong), Decision::H1);
+        let few = Score { pairs: [0, 0, 3] };
+        assert_eq!(sprt.decide(&few), Decision::Continue);
+        assert_eq!(sprt.llr(&Score::default()), 0.);
+    }
+    #[test]
+    fn no_humans() {
+        let specs = ["greedy".to_string(), "human".to_string()];
+        let e = run(&specs, &Opt::default()).unwrap_err();
+        println!("{}", e);
+        assert!(e.contains("human"));
+    }
+    #[test]
+    fn greedy_vs_random() {
+        let opt = Opt {
+            pairs: 20,
+            ..Opt::default()
+        };
+        let specs = ["greedy".to_string(), "random".to_string()];
+        let res = run(&specs, &opt).unwrap();
+        println!("{}", res[0].summary());
+        println!("{}", to_csv(&res));
+        #[cfg(feature = "serde")]
+        println!("{}", to_json(&res));
+        assert_eq!(res[0].score.games(), 40);
+    }
+}
@@ -0,0 +1,120 @@
+//! `KariuchiError`, the error of everything that builds, checks or reads game data.
+
+use std::fmt::{Display, Formatter};
+
+use crate::{
+    game::{Action, NUM_PIECES},
+    position::Position,
+};
+
+/// Why a square, throw, move or saved text could not be built, applied or read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KariuchiError {
+    /// No square has this index.
+    NoSuchPosition(usize),
+    /// The outer route has no square with this number.
+    NoSuchRoute(usize),
+    /// There is no step `x` on shortcut `path`.
+    NoSuchShortcut {
+        /// The shortcut, counted from 0.
+        path: usize,
+        /// The step along it.
+        x: usize,
+    },
+    /// No throw moves this many steps.
+    NoSuchThrow(usize),
+    /// A move must take between one and `NUM_PIECES` pieces.
+    BadCount(usize),
+    /// Pieces at the goal never move again.
+    FromGoal,
+    /// No move ends at the start.
+    ToStart,
+    /// A move must leave the square it starts from.
+    Stays,
+    /// The side to move has only `have` pieces on `at`, fewer than the move takes.
+    NotEnoughPieces {
+        /// Where the move starts.
+        at: Position,
+        /// Pieces of the side to move there.
+        have: usize,
+        /// Pieces the move takes.
+        num: usize,
+    },
+    /// Nobody moves once a side has all its pieces at the goal.
+    GameOver,
+    /// The move is not among `State::actions` for its throw.
+    Illegal(Action, Illegal),
+    /// Text that names no square, like `R30`.
+    BadPosition(String),
+    /// Text that is not a number of steps, like `x`.
+    BadThrow(String),
+    /// A side lists this many pieces instead of `NUM_PIECES`.
+    PieceCount(usize),
+    /// A finished game whose winner is not the side with all its pieces at the goal.
+    WrongWinner,
+    /// Text laid out neither as `State` nor as `Record` writes it; the message says where.
+    Syntax(String),
+    /// A line of a game record that is not a move.
+    BadMove {
+        /// The line as written.
+        line: String,
+        /// What is wrong with it.
+        error: Box<KariuchiError>,
+    },
+    /// Move `n` of a game record, counting from 1, cannot be played.
+    AtMove(usize, Box<KariuchiError>),
+}
+
+/// Why `State::check` rejects a well-formed move while the pieces to move are there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Illegal {
+    /// Pieces leave the start one at a time.
+    FromStart,
+    /// `to` is not where the throw leads from `at`.
+    Unreachable,
+}
+
+impl Display for KariuchiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KariuchiError::NoSuchPosition(x) => write!(f, "no position {}", x),
+            KariuchiError::NoSuchRoute(x) => write!(f, "no route square {}", x),
+            KariuchiError::NoSuchShortcut { path, x } => {
+                write!(f, "no shortcut square {} on path {}", x, path)
+            }
+            KariuchiError::NoSuchThrow(x) => write!(f, "no throw of {}", x),
+            KariuchiError::BadCount(num) => write!(f, "cannot move {} pieces", num),
+            KariuchiError::FromGoal => write!(f, "cannot move from the goal"),
+            KariuchiError::ToStart => write!(f, "cannot move to the start"),
+            KariuchiError::Stays => write!(f, "cannot move to the same square"),
+            KariuchiError::NotEnoughPieces { at, have, num } => {
+                write!(f, "{} pieces on {}, cannot move {}", have, at, num)
+            }
+            KariuchiError::GameOver => write!(f, "the game is over"),
+            KariuchiError::Illegal(act, why) => match why {
+                Illegal::FromStart => {
+                    write!(f, "illegal {}: one piece leaves the start at a time", act)
+                }
+                Illegal::Unreachable => write!(
+                    f,
+                    "illegal {}: a throw of {} does not lead from {} to {}",
+                    act,
+                    usize::from(act.sticks()),
+                    act.at(),
+                    act.to()
+                ),
+            },
+            KariuchiError::BadPosition(text) => write!(f, "bad position `{}`", text),
+            KariuchiError::BadThrow(text) => write!(f, "bad throw `{}`", text),
+            KariuchiError::PieceCount(n) => {
+                write!(f, "expected {} pieces, got {}", NUM_PIECES, n)
+            }
+            KariuchiError::WrongWinner => write!(f, "the winner does not match the pieces"),
+            KariuchiError::Syntax(why) => write!(f, "{}", why),
+            KariuchiError::BadMove { line, error } => write!(f, "bad move `{}`: {}", line, error),
+            KariuchiError::AtMove(n, error) => write!(f, "move {}: {}", n, error),
+        }
+    }
+}
+
+impl std::error::Error for KariuchiError {}
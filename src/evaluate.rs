@@ -1,24 +1,216 @@
-use crate::{game::State, sticks::Sticks};
+//! Win probability estimates: full-width expectimax over the throws and moves, with random
+//! playouts at the leaves.
 
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use crate::{
+    game::{Action, State},
+    packed::Packed,
+    stats::Stats,
+    sticks::Sticks,
+    trace::{Chance, Reply, Trace},
+};
+
+/// Search settings: full-width expectimax `ply` deep, then `rollout` random playouts per leaf.
 #[derive(Debug, Clone, Copy)]
 pub struct Opt {
+    /// Throws and moves searched in full before the playouts.
     pub ply: usize,
+    /// Playouts averaged at each leaf.
     pub rollout: usize,
 }
 
+/// The value of one throw in `Evaluate::breakdown`.
+#[derive(Debug, Clone, Copy)]
+pub struct ThrowValue {
+    /// Chance of the throw.
+    pub probability: f64,
+    /// The throw.
+    pub sticks: Sticks,
+    /// The move the search prefers for this throw.
+    pub best: Action,
+    /// Win probability of the side that threw, after playing `best`.
+    pub value: f64,
+}
+
+/// The evaluation of a position split by throw; `value` is the probability-weighted sum.
+#[derive(Debug, Clone)]
+pub struct Breakdown {
+    /// One entry per throw, as `Sticks::all_sticks` lists them.
+    pub throws: Vec<ThrowValue>,
+    /// Win probability of the side to move.
+    pub value: f64,
+}
+
+/// Estimates the probability that the side to move in `state` wins.
 #[derive(Debug, Clone)]
 pub struct Evaluate {
     option: Opt,
     state: State,
+    stop: Option<Arc<AtomicBool>>,
+    deadline: Option<Instant>,
+    seed: Option<u64>,
+    stats: Option<Arc<Stats>>,
 }
 
 impl Evaluate {
+    /// A search of `state` with `option`, without a stop, deadline, seed or stats.
     pub fn new(option: Opt, state: State) -> Self {
-        Evaluate { option, state }
+        Evaluate {
+            option,
+            state,
+            stop: None,
+            deadline: None,
+            seed: None,
+            stats: None,
+        }
+    }
+    /// Abandons the search once `stop` is set.
+    pub fn with_stop(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = Some(stop);
+        self
     }
+    /// Abandons the search once `deadline` has passed.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+    /// Seeds the playouts from each leaf with `seed` and the leaf's key, so the same search
+    /// always returns the same value.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+    /// Counts the positions, chance nodes and playouts of the search in `stats`.
+    pub fn with_stats(mut self, stats: Arc<Stats>) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+    /// Win probability of the side to move, searched `ply` deep. Meaningless if the search
+    /// was stopped or ran out of time; `try_evaluate` tells.
     pub fn evaluate(&self) -> f64 {
         self.expand(&self.state, self.option.ply)
     }
+    /// Like `evaluate`, but `None` if the search was stopped or ran out of time.
+    pub fn try_evaluate(&self) -> Option<f64> {
+        let p = self.evaluate();
+        if self.aborted() {
+            None
+        } else {
+            Some(p)
+        }
+    }
+    /// Searches ply 0, 1, ... up to `Opt::ply` and returns the deepest finished ply with its
+    /// value, or `None` if not even ply 0 finished before a stop or the deadline.
+    pub fn evaluate_deepening(&self) -> Option<(usize, f64)> {
+        let mut best = None;
+        for ply in 0..=self.option.ply {
+            let p = self.expand(&self.state, ply);
+            if self.aborted() {
+                break;
+            }
+            best = Some((ply, p));
+        }
+        best
+    }
+    /// What `evaluate` adds up: the best move and its value for every throw. Searches at
+    /// least one ply; `None` once the game is over.
+    pub fn breakdown(&self) -> Option<Breakdown> {
+        if self.state.is_end() {
+            return None;
+        }
+        let ply = self.option.ply.max(1);
+        let mut throws = vec![];
+        let mut value = 0.;
+        for (p, s) in Sticks::all_sticks() {
+            let (best, v) = self.best(&self.state, ply, s);
+            value += p * v;
+            throws.push(ThrowValue {
+                probability: p,
+                sticks: s,
+                best,
+                value: v,
+            });
+        }
+        Some(Breakdown { throws, value })
+    }
+    /// Searches every move for `sticks` like `agent::evaluate_par` does and keeps the
+    /// tree: the value of each alternative, the best reply to every throw along the expected
+    /// line after the best move, and the nodes searched per depth.
+    pub fn trace(&self, sticks: Sticks) -> Trace {
+        self.trace_tries(sticks, 1)
+    }
+    /// `trace` repeated `tries` times in parallel. Each alternative takes its mean value,
+    /// the nodes are summed over the searches and the line is the first search's. The best
+    /// move is the last of the highest values, as `agent::Expectimax` picks it.
+    pub fn trace_tries(&self, sticks: Sticks, tries: usize) -> Trace {
+        use rayon::prelude::*;
+        let tries = tries.max(1);
+        let runs: Vec<_> = (0..tries)
+            .into_par_iter()
+            .map(|_| {
+                let mut nodes = vec![1];
+                let alternatives =
+                    self.best_traced(&self.state, self.option.ply + 1, sticks, 1, &mut nodes);
+                (alternatives, nodes)
+            })
+            .collect();
+        let mut runs = runs.into_iter();
+        let (mut alternatives, mut nodes) = runs.next().unwrap();
+        for (alts, n) in runs {
+            for (a, b) in alternatives.iter_mut().zip(alts) {
+                a.1 += b.1;
+            }
+            if nodes.len() < n.len() {
+                nodes.resize(n.len(), 0);
+            }
+            for (a, b) in nodes.iter_mut().zip(n) {
+                *a += b;
+            }
+        }
+        for a in alternatives.iter_mut() {
+            a.1 /= tries as f64;
+        }
+        let best = alternatives
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i);
+        let line = best.and_then(|i| {
+            let mut best = alternatives.remove(i);
+            alternatives.sort_by(|a, b| b.1.total_cmp(&a.1));
+            let line = best.2.take();
+            alternatives.insert(0, best);
+            line
+        });
+        Trace {
+            state: self.state.clone(),
+            sticks,
+            alternatives: alternatives.into_iter().map(|(a, v, _)| (a, v)).collect(),
+            line,
+            nodes,
+        }
+    }
+    fn aborted(&self) -> bool {
+        if let Some(stop) = &self.stop {
+            if stop.load(Ordering::Relaxed) {
+                return true;
+            }
+        }
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                return true;
+            }
+        }
+        false
+    }
     fn leaf(state: &State, first: bool) -> Option<f64> {
         if state.is_end() {
             if state.is_win_first() == first {
@@ -30,12 +222,21 @@ impl Evaluate {
         None
     }
     fn expand(&self, state: &State, ply: usize) -> f64 {
+        if let Some(stats) = &self.stats {
+            stats.node(self.option.ply.saturating_sub(ply));
+        }
         if let Some(p) = Self::leaf(state, state.is_first()) {
             return p;
         }
+        if self.aborted() {
+            return 0.;
+        }
         if ply == 0 {
             return self.rollout(state);
         }
+        if let Some(stats) = &self.stats {
+            stats.chance();
+        }
         let sticks = Sticks::all_sticks();
         let mut sum = 0.;
         for (p, s) in sticks {
@@ -45,43 +246,192 @@ impl Evaluate {
         sum
     }
     fn solve(&self, state: &State, ply: usize, sticks: Sticks) -> f64 {
+        self.best(state, ply, sticks).1
+    }
+    fn best(&self, state: &State, ply: usize, sticks: Sticks) -> (Action, f64) {
         let actions = state.actions(sticks);
-        let mut res = 0.;
+        let mut res = (actions[0], 0.);
         for act in actions {
             let mut s = state.clone();
             s.act(act);
             let p = self.expand(&s, ply - 1);
             let p = if sticks.replay() { p } else { 1. - p };
-            if p > res {
-                res = p;
+            if p > res.1 {
+                res = (act, p);
             }
         }
         res
     }
+    // `expand` keeping the best reply to each throw, counting positions at `depth`.
+    fn expand_traced(
+        &self,
+        state: &State,
+        ply: usize,
+        depth: usize,
+        nodes: &mut Vec<usize>,
+    ) -> (f64, Option<Chance>) {
+        if nodes.len() <= depth {
+            nodes.resize(depth + 1, 0);
+        }
+        nodes[depth] += 1;
+        if let Some(p) = Self::leaf(state, state.is_first()) {
+            return (p, None);
+        }
+        if ply == 0 {
+            return (self.rollout(state), None);
+        }
+        let mut value = 0.;
+        let mut replies = vec![];
+        for (p, sticks) in Sticks::all_sticks() {
+            let mut alternatives = self.best_traced(state, ply, sticks, depth + 1, nodes);
+            let i = (0..alternatives.len())
+                .max_by(|a, b| alternatives[*a].1.total_cmp(&alternatives[*b].1))
+                .unwrap();
+            let (best, v, next) = alternatives.swap_remove(i);
+            value += p * v;
+            replies.push(Reply {
+                sticks,
+                probability: p,
+                best,
+                value: v,
+                next,
+            });
+        }
+        let chance = Chance {
+            state: state.clone(),
+            value,
+            replies,
+        };
+        (value, Some(chance))
+    }
+    // Every move for `sticks` with its value for the mover and the tree below it.
+    fn best_traced(
+        &self,
+        state: &State,
+        ply: usize,
+        sticks: Sticks,
+        depth: usize,
+        nodes: &mut Vec<usize>,
+    ) -> Vec<(Action, f64, Option<Chance>)> {
+        state
+            .actions(sticks)
+            .into_iter()
+            .map(|act| {
+                let mut s = state.clone();
+                s.act(act);
+                let (p, next) = self.expand_traced(&s, ply - 1, depth, nodes);
+                let p = if sticks.replay() { p } else { 1. - p };
+                (act, p, next)
+            })
+            .collect()
+    }
     fn rollout(&self, state: &State) -> f64 {
+        match self.seed {
+            Some(seed) => self.rollout_with(state, &mut StdRng::seed_from_u64(seed ^ state.key())),
+            None => self.rollout_with(state, &mut rand::thread_rng()),
+        }
+    }
+    fn rollout_with<R: Rng>(&self, state: &State, rng: &mut R) -> f64 {
         let mut sum = 0.;
         for _ in 0..self.option.rollout {
-            let s = state.clone();
-            sum += self.rollout_once(s);
+            if self.aborted() {
+                return 0.;
+            }
+            let (p, moves) = Self::playout(state.clone(), rng);
+            if let Some(stats) = &self.stats {
+                stats.rollout(moves);
+            }
+            sum += p;
         }
         sum / self.option.rollout as f64
     }
-    fn rollout_once(&self, mut state: State) -> f64 {
-        let first = state.is_first();
-        while !state.is_end() {
-            let sticks = Sticks::throw();
-            let actions = state.actions(sticks);
-            let num = rand::random::<usize>() % actions.len();
-            state.act(actions[num])
+    /// Plays random moves to the end; 1.0 if the side to move in `state` won, else 0.0.
+    pub fn rollout_once(state: State) -> f64 {
+        Self::rollout_once_with(state, &mut rand::thread_rng())
+    }
+    /// `rollout_once` drawing throws and moves from `rng`.
+    pub fn rollout_once_with<R: Rng>(state: State, rng: &mut R) -> f64 {
+        Self::playout(state, rng).0
+    }
+    // `rollout_once_with` and the number of moves played.
+    fn playout<R: Rng>(state: State, rng: &mut R) -> (f64, usize) {
+        let mut p = Packed::from(&state);
+        let first = p.is_first();
+        let mut actions = Vec::with_capacity(16);
+        let mut moves = 0;
+        while !p.is_end() {
+            let sticks = Sticks::throw_with(rng);
+            p.actions_into(sticks, &mut actions);
+            p.act(actions[rng.gen_range(0..actions.len())]);
+            moves += 1;
         }
-        Self::leaf(&state, first).unwrap()
+        let value = if p.is_win_first() == first { 1.0 } else { 0.0 };
+        (value, moves)
     }
 }
 
+/// Static evaluation: how many steps ahead `first`'s side is in the race to the goal.
+pub fn progress(state: &State, first: bool) -> isize {
+    let remain = |side: bool| {
+        state
+            .pieces(side)
+            .iter()
+            .map(|p| p.steps_to_goal() as isize)
+            .sum::<isize>()
+    };
+    remain(!first) - remain(first)
+}
+
+/// The `equity` subcommand: lists every throw of the position in POSITION_FILE (or stdin)
+/// with its best move.
+pub fn main(args: &[String]) -> Result<(), String> {
+    let mut opt = Opt {
+        ply: 2,
+        rollout: 20,
+    };
+    let mut input = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--ply" => opt.ply = value()?.parse().map_err(|e| format!("--ply: {}", e))?,
+            "--rollout" => {
+                opt.rollout = value()?.parse().map_err(|e| format!("--rollout: {}", e))?
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => input = Some(arg.clone()),
+        }
+    }
+    if opt.rollout == 0 {
+        return Err("--rollout must be positive".to_string());
+    }
+    let state = crate::game::read_position(input)?;
+    let mut b = Evaluate::new(opt, state.clone())
+        .breakdown()
+        .ok_or("the game is over")?;
+    b.throws.sort_by_key(|t| t.sticks);
+    println!("{}", state.visual());
+    println!(
+        "{:<7}{:>7}  {:<32}{:>7}",
+        "throw", "prob", "best move", "value"
+    );
+    for t in &b.throws {
+        println!(
+            "{:<7}{:>6.2}%  {:<32}{:>7.3}",
+            usize::from(t.sticks),
+            t.probability * 100.,
+            state.describe(t.best).to_string(),
+            t.value
+        );
+    }
+    println!("{:<48}{:>7.3}", "expected", b.value);
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{game::Action, position::Position};
+    use crate::{game::NUM_PIECES, position::Position};
 
     #[test]
     fn evaluate() {
@@ -99,6 +449,33 @@ mod tests {
         println!("{}", p);
     }
     #[test]
+    fn breakdown() {
+        let g = Position::goal();
+        let r29 = Position::route(29);
+        let state = State::from_pieces([g, g, g, r29], [g, g, g, r29], true);
+        let b = Evaluate::new(Opt { ply: 1, rollout: 4 }, state)
+            .breakdown()
+            .unwrap();
+        let mut sum = 0.;
+        for t in &b.throws {
+            println!(
+                "{:?} {:.4} {} {:.3}",
+                t.sticks, t.probability, t.best, t.value
+            );
+            sum += t.probability * t.value;
+            if usize::from(t.sticks) > 1 {
+                assert_eq!(t.value, 1.);
+                assert_eq!(t.best.to(), g);
+            }
+        }
+        assert_eq!(b.throws.len(), Sticks::all_sticks().len());
+        assert!((sum - b.value).abs() < 1e-12);
+        let over = State::from_pieces([g; NUM_PIECES], [r29; NUM_PIECES], false);
+        assert!(Evaluate::new(Opt { ply: 1, rollout: 4 }, over)
+            .breakdown()
+            .is_none());
+    }
+    #[test]
     fn almost_win() {
         let opt = Opt { ply: 2, rollout: 2 };
         let mut state = State::new();
@@ -117,4 +494,72 @@ mod tests {
         let p = e.evaluate();
         println!("{}", p);
     }
+    #[test]
+    fn stopped() {
+        let opt = Opt {
+            ply: 3,
+            rollout: 100,
+        };
+        let stop = Arc::new(AtomicBool::new(true));
+        let e = Evaluate::new(opt, State::new()).with_stop(stop);
+        assert_eq!(e.try_evaluate(), None);
+        assert_eq!(e.evaluate_deepening(), None);
+        let e = Evaluate::new(opt, State::new()).with_deadline(Instant::now());
+        assert_eq!(e.try_evaluate(), None);
+    }
+    #[test]
+    fn seeded() {
+        let opt = Opt { ply: 1, rollout: 8 };
+        let run = |seed| Evaluate::new(opt, State::new()).with_seed(seed).evaluate();
+        let p = run(5);
+        println!("{} {}", p, run(6));
+        assert_eq!(p, run(5));
+        let mut a = StdRng::seed_from_u64(9);
+        let mut b = StdRng::seed_from_u64(9);
+        for _ in 0..20 {
+            assert_eq!(
+                Evaluate::rollout_once_with(State::new(), &mut a),
+                Evaluate::rollout_once_with(State::new(), &mut b)
+            );
+        }
+    }
+    #[test]
+    fn stats() {
+        let opt = Opt { ply: 2, rollout: 3 };
+        let stats = Arc::new(Stats::new(opt.ply));
+        Evaluate::new(opt, State::new())
+            .with_stats(stats.clone())
+            .evaluate();
+        let nodes = stats.nodes();
+        println!(
+            "{:?} chance {} rollouts {} moves {}",
+            nodes,
+            stats.chance_nodes(),
+            stats.rollouts(),
+            stats.rollout_moves()
+        );
+        // From the start every throw has one move, so depth 1 holds one position per throw.
+        assert_eq!(nodes[0], 1);
+        assert_eq!(nodes[1], Sticks::all_sticks().len() as u64);
+        assert_eq!(stats.chance_nodes(), nodes[0] + nodes[1]);
+        assert_eq!(stats.rollouts(), nodes[2] * opt.rollout as u64);
+        assert!(stats.rollout_moves() > stats.rollouts());
+    }
+    #[test]
+    fn deepening() {
+        let opt = Opt { ply: 1, rollout: 2 };
+        let (ply, p) = Evaluate::new(opt, State::new())
+            .evaluate_deepening()
+            .unwrap();
+        assert_eq!(ply, 1);
+        assert!((0. ..=1.).contains(&p));
+    }
+    #[test]
+    fn progress_after_move() {
+        let mut state = State::new();
+        assert_eq!(progress(&state, true), 0);
+        state.act(state.actions(Sticks::from(3))[0]);
+        assert_eq!(progress(&state, true), 3);
+        assert_eq!(progress(&state, false), -3);
+    }
 }
@@ -1,55 +1,152 @@
-use std::fmt::{Display, Formatter};
+//! The rules: `State` holds a position and the side to move, and `Action` is one move of
+//! one or more stacked pieces.
+
+use std::{
+    fmt::{Display, Formatter},
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
 
 use crate::{
-    position::{self, Position, POSITIONS},
+    error::{Illegal, KariuchiError},
+    position::{self, HumanPosition, Position, OPP_SHORTCUTS, POSITIONS},
     sticks::Sticks,
+    zobrist,
 };
-const NUM_PIECES: usize = 4;
+/// Pieces per side.
+pub const NUM_PIECES: usize = 4;
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+/// A position of the game: where both sides' pieces stand, whose turn it is and who won.
+///
+/// `Display` writes the turn line followed by the pieces of the first and second side,
+/// and `FromStr` reads the same text back.
+///
+/// Pieces of a side are interchangeable, so equality compares the `canonical` forms and
+/// `Hash` uses the Zobrist `key`, which `act` keeps up to date.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(
+        try_from = "crate::serialize::StateRepr",
+        into = "crate::serialize::StateRepr"
+    )
+)]
 pub struct State {
     first_pos: [Position; NUM_PIECES],
     second_pos: [Position; NUM_PIECES],
     first_turn: bool,
     winner: isize,
+    key: u64,
 }
 
 impl State {
+    /// The opening position: every piece at the start and the first side to move.
     pub fn new() -> Self {
-        State {
-            first_pos: [Position::start(); NUM_PIECES],
-            second_pos: [Position::start(); NUM_PIECES],
-            first_turn: true,
+        Self::from_pieces(
+            [Position::start(); NUM_PIECES],
+            [Position::start(); NUM_PIECES],
+            true,
+        )
+    }
+    /// A state with the given pieces and side to move; the winner follows from the pieces.
+    pub fn from_pieces(
+        first_pos: [Position; NUM_PIECES],
+        second_pos: [Position; NUM_PIECES],
+        first_turn: bool,
+    ) -> Self {
+        let mut s = State {
+            first_pos,
+            second_pos,
+            first_turn,
             winner: 0,
-        }
+            key: zobrist::key(&first_pos, &second_pos, first_turn),
+        };
+        s.check_end();
+        s
     }
+    /// Applies a move of the side to move. Opponent pieces on `to` go back to the start.
+    ///
+    /// Panics if the side to move has fewer than `act.num()` pieces on `act.at()`; see
+    /// `try_act`.
     pub fn act(&mut self, act: Action) {
+        let pos = self.movers(act).unwrap_or_else(|e| panic!("{}", e));
+        self.apply(act, pos)
+    }
+    /// The strict `act`: only a move that passes `check` is applied. Anything else leaves
+    /// the state as it was and returns the reason.
+    pub fn try_act(&mut self, act: Action) -> Result<(), KariuchiError> {
+        self.check(act)?;
+        let pos = self.movers(act)?;
+        self.apply(act, pos);
+        Ok(())
+    }
+    /// Whether `act` is one of `actions(act.sticks())` while the game is on, and if not, why.
+    /// Never panics, whatever throw `act` carries.
+    pub fn check(&self, act: Action) -> Result<(), KariuchiError> {
+        if self.is_end() {
+            return Err(KariuchiError::GameOver);
+        }
+        Sticks::checked(usize::from(act.sticks))?;
+        self.movers(act)?;
+        if act.at == Position::start() && act.num > 1 {
+            return Err(KariuchiError::Illegal(act, Illegal::FromStart));
+        }
+        if !act.at.advance(usize::from(act.sticks)).contains(&act.to) {
+            return Err(KariuchiError::Illegal(act, Illegal::Unreachable));
+        }
+        Ok(())
+    }
+    /// Whether `check` accepts `act`.
+    pub fn is_legal(&self, act: Action) -> bool {
+        self.check(act).is_ok()
+    }
+    // Indices of the pieces `act` moves, or an error if too few stand on `at`.
+    fn movers(&self, act: Action) -> Result<Vec<usize>, KariuchiError> {
         let pos = self.get_piece(act.at);
-        assert!(!pos.is_empty());
-        assert!(act.num <= pos.len());
+        if pos.len() < act.num {
+            return Err(KariuchiError::NotEnoughPieces {
+                at: act.at,
+                have: pos.len(),
+                num: act.num,
+            });
+        }
+        Ok(pos)
+    }
+    fn apply(&mut self, act: Action, pos: Vec<usize>) {
+        let me = self.first_turn;
+        let count = |pieces: &[Position; NUM_PIECES], p: Position| {
+            pieces.iter().filter(|q| **q == p).count()
+        };
+        let (at, to) = (pos.len(), count(self.current_pieces(), act.to));
+        self.key ^= zobrist::piece(me, act.at, at) ^ zobrist::piece(me, act.at, at - act.num);
+        self.key ^= zobrist::piece(me, act.to, to) ^ zobrist::piece(me, act.to, to + act.num);
         for i in 0..act.num {
             self.current_pieces_mut()[pos[i]] = act.to;
         }
         if act.to != Position::goal() {
-            if self.first_turn {
-                for i in 0..NUM_PIECES {
-                    if self.second_pos[i] == act.to {
-                        self.second_pos[i] = Position::start();
-                    }
-                }
+            let theirs = if me {
+                &mut self.second_pos
             } else {
-                for i in 0..NUM_PIECES {
-                    if self.first_pos[i] == act.to {
-                        self.first_pos[i] = Position::start();
-                    }
+                &mut self.first_pos
+            };
+            let (captured, start) = (count(theirs, act.to), count(theirs, Position::start()));
+            for i in 0..NUM_PIECES {
+                if theirs[i] == act.to {
+                    theirs[i] = Position::start();
                 }
             }
+            self.key ^= zobrist::piece(!me, act.to, captured)
+                ^ zobrist::piece(!me, Position::start(), start)
+                ^ zobrist::piece(!me, Position::start(), start + captured);
         }
         self.check_end();
         if !act.sticks.replay() {
             self.first_turn = !self.first_turn;
+            self.key ^= zobrist::second_to_move();
         }
     }
+    /// Every move the side to move can make with `sticks`.
     pub fn actions(&self, sticks: Sticks) -> Vec<Action> {
         let my_pieces = self.my_pieces();
         let mut actions = vec![];
@@ -72,15 +169,40 @@ impl State {
         }
         actions
     }
+    /// The same position with each side's pieces in square order. The board has no other
+    /// symmetry: squares that move alike, such as `R17` and `K3_0`, are entered from
+    /// different squares, so they differ in what can capture them.
+    pub fn canonical(&self) -> State {
+        let mut s = self.clone();
+        s.first_pos.sort_unstable();
+        s.second_pos.sort_unstable();
+        s
+    }
+    /// Zobrist key of the pieces and the side to move, updated incrementally by `act`.
+    /// Positions that differ only in which of a side's pieces stand where share a key.
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+    /// Whether a side has all its pieces at the goal.
     pub fn is_end(&self) -> bool {
         self.winner != 0
     }
+    /// Whether the first side won; only meaningful once `is_end`.
     pub fn is_win_first(&self) -> bool {
         self.winner == 1
     }
+    /// Whether the first side is to move.
     pub fn is_first(&self) -> bool {
         self.first_turn
     }
+    /// Where the pieces of the first side, or of the second, stand.
+    pub fn pieces(&self, first: bool) -> &[Position; NUM_PIECES] {
+        if first {
+            &self.first_pos
+        } else {
+            &self.second_pos
+        }
+    }
     fn check_end(&mut self) {
         let mut first = true;
         let mut second = true;
@@ -132,6 +254,33 @@ impl State {
         }
         board
     }
+    /// What `act` would do: how the pieces travel, what they capture and whether the
+    /// move finishes or passes the turn. `act` must be a move of the side to move.
+    pub fn describe(&self, act: Action) -> Description {
+        let opponents = self.pieces(!self.first_turn);
+        let captured = if act.to == Position::goal() {
+            0
+        } else {
+            opponents.iter().filter(|p| **p == act.to).count()
+        };
+        let mut after = self.clone();
+        after.act(act);
+        let on_goal = |s: &State| {
+            s.pieces(self.first_turn)
+                .iter()
+                .filter(|p| **p == Position::goal())
+                .count()
+        };
+        Description {
+            action: act,
+            path: Path::of(act.at, act.to),
+            captured,
+            finished: on_goal(&after) - on_goal(self),
+            wins: after.is_end(),
+            passes_turn: !act.sticks.replay() && !after.is_end(),
+        }
+    }
+    /// The board as text: `O` marks the first side's pieces and `X` the second's.
     pub fn visual(&self) -> String {
         let mut board = vec![0; POSITIONS];
         for i in 0..NUM_PIECES {
@@ -145,7 +294,7 @@ impl State {
         for i in 0..POSITIONS {
             let (y, x) = alignment[i];
             let str = if board[i] == 0 {
-                format!("..")
+                "..".to_string()
             } else {
                 let mark = if board[i] > 0 { "O" } else { "X" };
                 let num = if board[i] > 0 { board[i] } else { -board[i] };
@@ -162,8 +311,9 @@ impl Display for State {
         if self.is_end() {
             writeln!(
                 f,
-                "end: winner {}",
-                if self.winner == 1 { "First" } else { "Second" }
+                "end: winner {}, turn: {}",
+                if self.winner == 1 { "First" } else { "Second" },
+                if self.first_turn { "first" } else { "second" }
             )?;
         } else {
             writeln!(
@@ -183,7 +333,94 @@ impl Display for State {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+impl PartialEq for State {
+    fn eq(&self, other: &Self) -> bool {
+        if self.key != other.key || self.first_turn != other.first_turn {
+            return false;
+        }
+        let (a, b) = (self.canonical(), other.canonical());
+        a.first_pos == b.first_pos && a.second_pos == b.second_pos
+    }
+}
+
+impl Eq for State {}
+
+impl Hash for State {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.key);
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromStr for State {
+    type Err = KariuchiError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let syntax = |why: String| KariuchiError::Syntax(why);
+        let mut lines = s.lines().map(|l| l.trim()).filter(|l| !l.is_empty());
+        let head = lines
+            .next()
+            .ok_or_else(|| syntax("empty state".to_string()))?;
+        let bad_header = || syntax(format!("bad header `{}`", head));
+        // A finished game keeps the side to move: a winning 4 or 5 leaves it with the winner.
+        // Without a turn, the side that did not win is to move, as after any other throw.
+        let (end, first_turn) = match head.split_once(", ") {
+            Some((end, "turn: first")) => (Some(end), true),
+            Some((end, "turn: second")) => (Some(end), false),
+            Some(_) => return Err(bad_header()),
+            None => match head {
+                "turn: first" => (None, true),
+                "turn: second" => (None, false),
+                "end: winner First" => (Some(head), false),
+                "end: winner Second" => (Some(head), true),
+                _ => return Err(bad_header()),
+            },
+        };
+        let body = lines
+            .next()
+            .ok_or_else(|| syntax("missing pieces".to_string()))?;
+        if let Some(extra) = lines.next() {
+            return Err(syntax(format!("unexpected `{}`", extra)));
+        }
+        let (first, second) = body
+            .split_once('/')
+            .ok_or_else(|| syntax("missing `/`".to_string()))?;
+        let side = |text: &str| -> Result<[Position; NUM_PIECES], KariuchiError> {
+            let v = text
+                .split_whitespace()
+                .map(|p| p.parse::<Position>())
+                .collect::<Result<Vec<_>, _>>()?;
+            v.try_into()
+                .map_err(|v: Vec<_>| KariuchiError::PieceCount(v.len()))
+        };
+        let state = State::from_pieces(side(first)?, side(second)?, first_turn);
+        let winner = match end {
+            None => 0,
+            Some("end: winner First") => 1,
+            Some("end: winner Second") => -1,
+            Some(_) => return Err(bad_header()),
+        };
+        if state.winner != winner {
+            return Err(KariuchiError::WrongWinner);
+        }
+        Ok(state)
+    }
+}
+
+/// Moving `num` pieces of the side to move from `at` to `to` with the throw `sticks`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(
+        try_from = "crate::serialize::ActionRepr",
+        into = "crate::serialize::ActionRepr"
+    )
+)]
 pub struct Action {
     at: Position,
     to: Position,
@@ -191,14 +428,54 @@ pub struct Action {
     sticks: Sticks,
 }
 impl Action {
+    /// Panics where `try_new` returns an error.
     pub fn new(at: Position, to: Position, num: usize, sticks: Sticks) -> Self {
-        assert!(num > 0);
-        Action {
+        Self::try_new(at, to, num, sticks).unwrap_or_else(|e| panic!("{}", e))
+    }
+    /// A move of `num` pieces, or an error unless `sticks` is a throw of 1 to 5, `num` is
+    /// 1 to `NUM_PIECES`, `at` is not the goal, `to` is not the start and the two differ.
+    /// Whether `to` can be reached is left to `State::actions`.
+    pub fn try_new(
+        at: Position,
+        to: Position,
+        num: usize,
+        sticks: Sticks,
+    ) -> Result<Self, KariuchiError> {
+        Sticks::checked(usize::from(sticks))?;
+        if num == 0 || num > NUM_PIECES {
+            return Err(KariuchiError::BadCount(num));
+        }
+        if at == Position::goal() {
+            return Err(KariuchiError::FromGoal);
+        }
+        if to == Position::start() {
+            return Err(KariuchiError::ToStart);
+        }
+        if at == to {
+            return Err(KariuchiError::Stays);
+        }
+        Ok(Action {
             at,
             to,
             num,
             sticks,
-        }
+        })
+    }
+    /// The square the pieces leave.
+    pub fn at(&self) -> Position {
+        self.at
+    }
+    /// The square the pieces land on.
+    pub fn to(&self) -> Position {
+        self.to
+    }
+    /// How many stacked pieces move together.
+    pub fn num(&self) -> usize {
+        self.num
+    }
+    /// The throw the move was played with.
+    pub fn sticks(&self) -> Sticks {
+        self.sticks
     }
 }
 impl Display for Action {
@@ -207,6 +484,96 @@ impl Display for Action {
     }
 }
 
+/// How a move travels over the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Path {
+    /// Along the outer route, possibly into the goal.
+    Route,
+    /// Into or along a shortcut without touching the center.
+    Shortcut,
+    /// Onto or across the center.
+    Center,
+}
+
+impl Path {
+    fn of(at: Position, to: Position) -> Path {
+        let inner = |p: Position| match p.into() {
+            HumanPosition::SHORTCUT(path, _) => path < OPP_SHORTCUTS,
+            _ => false,
+        };
+        let outer = |p: Position| match p.into() {
+            HumanPosition::SHORTCUT(path, _) => path >= OPP_SHORTCUTS,
+            _ => false,
+        };
+        match (at.into(), to.into()) {
+            (HumanPosition::CENTER, _) | (_, HumanPosition::CENTER) => Path::Center,
+            _ if !outer(at) && outer(to) => Path::Center,
+            _ if inner(to) || outer(at) => Path::Shortcut,
+            _ => Path::Route,
+        }
+    }
+}
+
+/// The effects of an `Action`, as returned by `State::describe`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Description {
+    /// The move described.
+    pub action: Action,
+    /// The way the pieces took.
+    pub path: Path,
+    /// Opponent pieces sent back to the start.
+    pub captured: usize,
+    /// Pieces of the mover arriving at the goal.
+    pub finished: usize,
+    /// Whether the move ends the game.
+    pub wins: bool,
+    /// Whether the opponent moves next.
+    pub passes_turn: bool,
+}
+
+impl Display for Description {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let act = &self.action;
+        write!(f, "{} -> {}", act.at, act.to)?;
+        if act.num > 1 {
+            write!(f, " x{}", act.num)?;
+        }
+        match self.path {
+            Path::Route => {}
+            Path::Shortcut => write!(f, ", shortcut")?,
+            Path::Center => write!(f, ", via center")?,
+        }
+        if self.captured > 0 {
+            write!(f, ", captures {}", self.captured)?;
+        }
+        if self.finished > 0 {
+            write!(f, ", {} home", self.finished)?;
+        }
+        if self.wins {
+            write!(f, ", wins")?;
+        } else if !self.passes_turn {
+            write!(f, ", again")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads a position, written as `State` displays it, from the file at `input` or, without
+/// one, from stdin.
+pub fn read_position(input: Option<String>) -> Result<State, String> {
+    let text = match input {
+        Some(path) => std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?,
+        None => std::io::read_to_string(std::io::stdin()).map_err(|e| e.to_string())?,
+    };
+    text.parse().map_err(|e: KariuchiError| e.to_string())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -285,8 +652,285 @@ mod test {
         assert_eq!(s.first_pos[0], Position::from(0));
     }
     #[test]
+    fn parse_state() {
+        let mut s = State::new();
+        s.act(s.actions(Sticks::from(3))[0]);
+        s.act(s.actions(Sticks::from(5))[0]);
+        assert_eq!(format!("{}", s).parse::<State>(), Ok(s.clone()));
+        let end = State::from_pieces([Position::goal(); NUM_PIECES], s.second_pos, false);
+        assert!(end.is_end());
+        assert_eq!(format!("{}", end).parse::<State>(), Ok(end.clone()));
+        // Won on a replay throw, so the winner is still to move.
+        let kept = State::from_pieces(*end.pieces(true), *end.pieces(false), true);
+        println!("{}", kept);
+        assert_eq!(format!("{}", kept).parse::<State>(), Ok(kept));
+        let old = format!(
+            "end: winner First\n{}",
+            end.to_string().lines().nth(1).unwrap()
+        );
+        assert_eq!(old.parse::<State>(), Ok(end));
+        assert!("end: winner First, turn: none\nG G G G / S S S S"
+            .parse::<State>()
+            .is_err());
+        assert_eq!(
+            "turn: first\nS S S / S S S S".parse::<State>(),
+            Err(KariuchiError::PieceCount(3))
+        );
+        assert_eq!(
+            "end: winner First\nS S S S / S S S S".parse::<State>(),
+            Err(KariuchiError::WrongWinner)
+        );
+    }
+    #[test]
+    fn describe() {
+        let mut s = State::new();
+        s.act(s.actions(Sticks::from(3))[0]);
+        let hit = s.actions(Sticks::from(3))[0];
+        let d = s.describe(hit);
+        println!("{}", d);
+        assert_eq!(d.path, Path::Route);
+        assert_eq!(d.captured, 1);
+        assert!(d.passes_turn);
+
+        let corner = Position::route(5);
+        let s = State::from_pieces(
+            [corner, corner, Position::start(), Position::start()],
+            [Position::start(); NUM_PIECES],
+            true,
+        );
+        for act in s.actions(Sticks::from(4)) {
+            let d = s.describe(act);
+            println!("{}", d);
+            assert!(!d.passes_turn);
+            let expect = if act.to == Position::route(9) || act.at == Position::start() {
+                Path::Route
+            } else {
+                Path::Center
+            };
+            assert_eq!(d.path, expect);
+        }
+        let d = s.describe(s.actions(Sticks::from(2))[4]);
+        assert_eq!(d.path, Path::Shortcut);
+        assert_eq!(d.action.num, 2);
+
+        let mut first = [Position::goal(); NUM_PIECES];
+        first[0] = Position::route(29);
+        let s = State::from_pieces(first, [Position::start(); NUM_PIECES], true);
+        let d = s.describe(s.actions(Sticks::from(2))[0]);
+        assert_eq!(d.finished, 1);
+        assert!(d.wins);
+        assert!(!d.passes_turn);
+    }
+    #[test]
     fn print_visual() {
         let s = State::new();
         println!("{}", s.visual());
     }
+    #[test]
+    fn try_act() {
+        let r = Position::route;
+        let mut s = State::new();
+        let bad = Action::new(r(2), r(5), 1, Sticks::from(3));
+        assert_eq!(
+            s.try_act(bad),
+            Err(KariuchiError::NotEnoughPieces {
+                at: r(2),
+                have: 0,
+                num: 1
+            })
+        );
+        let many = Action::new(Position::start(), r(3), 2, Sticks::from(3));
+        assert_eq!(
+            s.try_act(many),
+            Err(KariuchiError::Illegal(many, Illegal::FromStart))
+        );
+        let far = Action::new(Position::start(), r(4), 1, Sticks::from(3));
+        assert_eq!(
+            s.try_act(far),
+            Err(KariuchiError::Illegal(far, Illegal::Unreachable))
+        );
+        println!("{}", s.check(far).unwrap_err());
+        assert!(!s.is_legal(far));
+        assert!(s.is_legal(Action::new(Position::start(), r(3), 1, Sticks::from(3))));
+        s.try_act(Action::new(Position::start(), r(3), 1, Sticks::from(3)))
+            .unwrap();
+        let again = s.clone();
+        let err = s.try_act(Action::new(r(3), r(6), 1, Sticks::from(3)));
+        println!("{}", err.clone().unwrap_err());
+        assert!(err.is_err());
+        assert_eq!(s, again);
+        assert_eq!(s.key(), again.key());
+        let g = Position::goal();
+        let mut over = State::from_pieces([g; NUM_PIECES], [r(3); NUM_PIECES], false);
+        assert_eq!(
+            over.try_act(Action::new(r(3), r(6), 1, Sticks::from(3))),
+            Err(KariuchiError::GameOver)
+        );
+        assert_eq!(
+            Action::try_new(r(3), r(6), 0, Sticks::from(3)),
+            Err(KariuchiError::BadCount(0))
+        );
+        assert_eq!(
+            Action::try_new(r(3), r(6), NUM_PIECES + 1, Sticks::from(3)),
+            Err(KariuchiError::BadCount(NUM_PIECES + 1))
+        );
+        assert_eq!(
+            Action::try_new(Position::goal(), r(6), 1, Sticks::from(3)),
+            Err(KariuchiError::FromGoal)
+        );
+        assert_eq!(
+            Action::try_new(r(3), Position::start(), 1, Sticks::from(3)),
+            Err(KariuchiError::ToStart)
+        );
+        assert_eq!(
+            Action::try_new(r(3), r(3), 1, Sticks::from(3)),
+            Err(KariuchiError::Stays)
+        );
+    }
+    #[test]
+    fn try_new_throws() {
+        // A throw of 0 at a corner and a throw past the goal from the start used to
+        // panic in `try_act` instead of failing.
+        let r = Position::route;
+        assert_eq!(
+            Action::try_new(r(5), Position::shortcut(0, 0), 1, Sticks::from(0)),
+            Err(KariuchiError::NoSuchThrow(0))
+        );
+        assert_eq!(
+            Action::try_new(Position::start(), r(3), 1, Sticks::from(40)),
+            Err(KariuchiError::NoSuchThrow(40))
+        );
+        for n in 1..=5 {
+            assert!(Action::try_new(Position::start(), r(n), 1, Sticks::from(n)).is_ok());
+        }
+    }
+    #[test]
+    fn check_throws() {
+        // Built past `try_new`, as nothing else can; `check` must still refuse them.
+        let r = Position::route;
+        let mut s = State::new();
+        s.act(s.actions(Sticks::from(5))[0]);
+        let zero = Action {
+            at: r(5),
+            to: Position::shortcut(0, 0),
+            num: 1,
+            sticks: Sticks::from(0),
+        };
+        let far = Action {
+            at: Position::start(),
+            to: r(3),
+            num: 1,
+            sticks: Sticks::from(40),
+        };
+        for act in [zero, far] {
+            let n = usize::from(act.sticks);
+            assert_eq!(s.check(act), Err(KariuchiError::NoSuchThrow(n)));
+            assert!(!s.is_legal(act));
+            let before = s.clone();
+            assert_eq!(s.try_act(act), Err(KariuchiError::NoSuchThrow(n)));
+            assert_eq!(s, before);
+        }
+    }
+    #[test]
+    fn zobrist_incremental() {
+        use crate::tape::Tape;
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut captures = 0;
+        for seed in 0..200 {
+            let mut tape = Tape::seeded(seed);
+            let mut s = State::new();
+            while !s.is_end() {
+                let actions = s.actions(tape.throw().unwrap());
+                let act = actions[rng.gen_range(0..actions.len())];
+                captures += s.describe(act).captured;
+                s.act(act);
+                let scratch = zobrist::key(&s.first_pos, &s.second_pos, s.first_turn);
+                assert_eq!(s.key(), scratch, "after {} in\n{}", act, s);
+            }
+        }
+        println!("{} captures", captures);
+        assert!(captures > 0);
+        // Swapping two pieces of a side changes nothing.
+        let r = Position::route;
+        let a = State::from_pieces([r(1), r(2), r(3), r(4)], [r(5); NUM_PIECES], true);
+        let b = State::from_pieces([r(4), r(2), r(3), r(1)], [r(5); NUM_PIECES], true);
+        assert_eq!(a.key(), b.key());
+    }
+    #[test]
+    fn canonical() {
+        use std::collections::HashSet;
+        let r = Position::route;
+        let a = State::from_pieces(
+            [r(4), r(2), Position::start(), r(4)],
+            [r(5), r(1), r(5), r(9)],
+            false,
+        );
+        let c = a.canonical();
+        println!("{}{}", a, c);
+        assert_eq!(c.first_pos, [Position::start(), r(2), r(4), r(4)]);
+        assert_eq!(c.second_pos, [r(1), r(5), r(5), r(9)]);
+        assert_eq!(a, c);
+        assert_eq!(c.canonical().first_pos, c.first_pos);
+        let set: HashSet<State> = [a.clone(), c.clone()].into_iter().collect();
+        assert_eq!(set.len(), 1);
+        let mut other = c.clone();
+        other.act(other.actions(Sticks::from(1))[0]);
+        assert_ne!(other, c);
+        let turn = State::from_pieces(c.first_pos, c.second_pos, true);
+        assert_ne!(turn, c);
+    }
+    #[test]
+    fn no_board_symmetry() {
+        // Any symmetry of the board must map squares to squares that move alike and are
+        // entered from squares that move alike. Refine by both until nothing changes.
+        let mut class: Vec<usize> = (0..POSITIONS)
+            .map(|i| match Position::from(i).into() {
+                HumanPosition::START => 0,
+                HumanPosition::GOAL => 1,
+                _ => 2,
+            })
+            .collect();
+        loop {
+            let sig: Vec<(usize, Vec<Vec<usize>>)> = (0..POSITIONS)
+                .map(|i| {
+                    // Classes reached with each throw, then classes entered from.
+                    let mut next = vec![];
+                    let mut prev = vec![];
+                    for d in 1..=5 {
+                        let mut v: Vec<usize> = Position::from(i)
+                            .advance(d)
+                            .iter()
+                            .map(|q| class[usize::from(*q)])
+                            .collect();
+                        v.sort();
+                        next.push(v);
+                        let mut v: Vec<usize> = (0..POSITIONS)
+                            .filter(|j| Position::from(*j).advance(d).contains(&Position::from(i)))
+                            .map(|j| class[j])
+                            .collect();
+                        v.sort();
+                        prev.push(v);
+                    }
+                    next.extend(prev);
+                    (class[i], next)
+                })
+                .collect();
+            let mut kinds = sig.clone();
+            kinds.sort();
+            kinds.dedup();
+            let before = class.iter().max().unwrap() + 1;
+            class = sig
+                .iter()
+                .map(|s| kinds.binary_search(s).unwrap())
+                .collect();
+            if kinds.len() == before {
+                break;
+            }
+        }
+        let mut kinds = class.clone();
+        kinds.sort();
+        kinds.dedup();
+        assert_eq!(kinds.len(), POSITIONS);
+    }
 }
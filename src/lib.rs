@@ -0,0 +1,61 @@
+//! Rules engine and solver for kariuchi, the ancient Japanese race game
+//! (<https://www.nabunken.go.jp/research/kariuchi.html>).
+//!
+//! - [`game::State`] holds a position and applies [`game::Action`]s; [`packed::Packed`]
+//!   is a compact copy of it used for fast playouts.
+//! - [`position::Position`] names the squares of the board.
+//! - [`sticks::Sticks`] is the outcome of a throw.
+//! - [`evaluate::Evaluate`] estimates win probabilities by expectimax search and rollouts,
+//!   can keep its search tree as a [`trace::Trace`] and counts its work in [`stats::Stats`].
+//! - [`agent::Agent`] players can be paired in games and [`arena`] matches,
+//!   throwing from a [`tape::Tape`].
+//! - [`record::Record`] keeps the moves of a game for replay and for review by [`analysis`]
+//!   and [`timeline`].
+//! - [`perft`] counts move paths to catch rule regressions.
+//! - The `try_` and `checked` constructors and [`game::State::try_act`] report bad input as
+//!   an [`error::KariuchiError`] where the plain versions panic, as do the parsers of saved
+//!   positions, games and tapes; [`game::State::check`] says why a move is illegal.
+//!
+//! With the `serde` feature, `State`, `Action`, `Position` and `Sticks` implement
+//! `Serialize` and `Deserialize`. The `server` feature adds a local HTTP/JSON analysis
+//! server in [`server`], the `ws` feature a WebSocket server for live games in [`ws`],
+//! the `tui` feature a full-screen terminal UI in [`tui`], and the `gif` feature animated
+//! replays in [`replay`].
+//!
+//! ```
+//! use kariuchi_solver::{game::State, sticks::Sticks};
+//!
+//! let mut state = State::new();
+//! let actions = state.actions(Sticks::from(3));
+//! state.act(actions[0]);
+//! assert!(!state.is_first());
+//! ```
+#![warn(missing_docs)]
+#![allow(clippy::needless_range_loop, clippy::upper_case_acronyms)]
+pub mod agent;
+pub mod analysis;
+pub mod arena;
+pub mod error;
+pub mod evaluate;
+pub mod game;
+pub mod packed;
+pub mod perft;
+pub mod position;
+pub mod record;
+#[cfg(feature = "gif")]
+pub mod replay;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod stats;
+pub mod sticks;
+pub mod svg;
+pub mod tape;
+pub mod timeline;
+pub mod trace;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "ws")]
+pub mod ws;
+pub mod zobrist;
@@ -1,21 +1,82 @@
-mod evaluate;
-mod game;
-mod position;
-mod sticks;
+use kariuchi_solver::{
+    agent, analysis, arena, evaluate, game, perft, record, svg, tape, timeline, trace,
+};
+
+// A subcommand, given the arguments after its name.
+type Command = fn(&[String]) -> Result<(), String>;
 
 fn main() {
-    let mut s = game::State::new();
-    let opt = evaluate::Opt {
-        ply: 3,
-        rollout: 10,
+    // The CPU's search statistics are shown after each move unless RUST_LOG says otherwise.
+    let filter = "warn,kariuchi_solver::stats=info";
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(filter)).init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command: Option<Command> = match args.first().map(|s| s.as_str()) {
+        Some("arena") => Some(arena::main),
+        Some("analyze") => Some(analysis::main),
+        Some("timeline") => Some(timeline::main),
+        Some("equity") => Some(evaluate::main),
+        Some("pv") => Some(trace::main),
+        Some("perft") => Some(perft::main),
+        Some("svg") => Some(svg::main),
+        #[cfg(feature = "gif")]
+        Some("gif") => Some(kariuchi_solver::replay::main),
+        #[cfg(feature = "tui")]
+        Some("tui") => Some(kariuchi_solver::tui::main),
+        #[cfg(feature = "ws")]
+        Some("ws") => Some(kariuchi_solver::ws::main),
+        #[cfg(feature = "server")]
+        Some("serve") => Some(kariuchi_solver::server::main),
+        _ => None,
+    };
+    if let Some(command) = command {
+        if let Err(e) = command(&args[1..]) {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        }
+        return;
+    }
+    let (specs, mut tape, save, save_game, timeline_csv) = match parse_args(&args) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let first = specs.first().map(|s| s.as_str()).unwrap_or("human");
+    let second = specs
+        .get(1)
+        .map(|s| s.as_str())
+        .unwrap_or("expectimax:ply=3,rollout=10,tries=3");
+    let mut agents = match (agent::from_spec(first), agent::from_spec(second)) {
+        (Ok(a), Ok(b)) => [a, b],
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("Error: {}", e);
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
     };
+    for a in agents.iter_mut() {
+        a.set_progress(true);
+    }
+    let mut s = game::State::new();
+    let mut rec = record::Record::new(s.clone());
+    let mut line = timeline_csv.as_ref().map(|_| {
+        timeline::Timeline::new(evaluate::Opt {
+            ply: 1,
+            rollout: 20,
+        })
+    });
     loop {
         println!("{}", s);
         println!("{}", s.visual());
         if s.is_end() {
             break;
         }
-        let sticks = sticks::Sticks::throw();
+        let Some(sticks) = tape.throw() else {
+            println!("Tape ended");
+            break;
+        };
         print!("Throw {}", usize::from(sticks));
         if sticks.replay() {
             println!(" (replay)");
@@ -23,103 +84,103 @@ fn main() {
             println!();
         }
         let actions = s.actions(sticks);
-        let tries = 3;
-        let p = evaluate_par(s.clone(), &actions, opt, tries);
-        for i in 0..actions.len() {
-            print!("- {} : {: <15} [", i, format!("{}", actions[i]));
-            for k in 0..tries {
-                print!("{:0.4}", p[i][k]);
-                if k < tries - 1 {
-                    print!(", ");
-                } else {
-                    println!("]")
+        let agent = &mut agents[if s.is_first() { 0 } else { 1 }];
+        let act = agent.choose(&s, sticks, &actions);
+        if let Some(info) = agent.info() {
+            println!("{}", info);
+        }
+        if let Err(e) = s.check(act) {
+            eprintln!("Error: {}: {}", agent.name(), e);
+            std::process::exit(1);
+        }
+        println!("{} {}", agent.name(), s.describe(act));
+        if let Some(t) = line.as_mut() {
+            let p = match t.push(&s, act) {
+                Ok(p) => p,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
                 }
-            }
+            };
+            println!(
+                "First wins {:.1}% -> {:.1}% after the throw -> {:.1}% after the move",
+                p.before * 100.,
+                p.after_throw * 100.,
+                p.after_move * 100.
+            );
         }
-        if s.is_first() {
-            let num = read_int(actions.len());
-            let act = actions[num];
-            println!("Player {}", act);
-            s.act(act);
-        } else {
-            let i = p
-                .iter()
-                .map(|a| a.iter().sum::<f64>())
-                .enumerate()
-                .max_by(|(_, a), (_, b)| a.total_cmp(b))
-                .map(|(index, _)| index)
-                .unwrap();
-            let act = actions[i];
-            println!("CPU {}", act);
-            s.act(act);
+        rec.push(act);
+        s.act(act);
+    }
+    if let Some(path) = save {
+        if let Err(e) = std::fs::write(&path, tape.save()) {
+            eprintln!("Error: {}: {}", path, e);
+        }
+    }
+    if let (Some(path), Some(t)) = (timeline_csv, line) {
+        print!("{}", t.summary());
+        if let Err(e) = std::fs::write(&path, t.to_csv()) {
+            eprintln!("Error: {}: {}", path, e);
+        }
+    }
+    if let Some(path) = save_game {
+        if let Err(e) = std::fs::write(&path, rec.save()) {
+            eprintln!("Error: {}: {}", path, e);
         }
     }
 }
 
-fn evaluate_par(
-    state: game::State,
-    actions: &[game::Action],
-    opt: evaluate::Opt,
-    tries: usize,
-) -> Vec<Vec<f64>> {
-    use rayon::prelude::*;
-    use std::io::Write;
-    use std::sync::Mutex;
-    let solved = std::sync::Arc::<Mutex<usize>>::new(Mutex::new(0));
-    let max = actions.len() * tries;
-    print!("{} / {}", solved.lock().unwrap(), max);
-    std::io::stdout().flush().unwrap();
+const USAGE: &str = "usage: kariuchi-solver [--seed S | --tape FILE] [--save-tape FILE] [--save-game FILE]
+                       [--timeline CSV_FILE] [FIRST_AGENT] [SECOND_AGENT]
+       kariuchi-solver arena ...
+       kariuchi-solver analyze [--ply N] [--rollout N] [--tries N] [--json] GAME_FILE
+       kariuchi-solver timeline [--ply N] [--rollout N] [--csv FILE] [--svg FILE] GAME_FILE
+       kariuchi-solver equity [--ply N] [--rollout N] [POSITION_FILE]
+       kariuchi-solver pv --sticks N [--ply N] [--rollout N] [--json] [POSITION_FILE]
+       kariuchi-solver perft [--depth N] [--divide] [POSITION_FILE]
+       kariuchi-solver svg [--sticks N] [--out FILE] [POSITION_FILE]
+       kariuchi-solver serve [--port P]  (with the `server` feature)
+       kariuchi-solver ws [--port P]     (with the `ws` feature)
+       kariuchi-solver tui [OPPONENT]    (with the `tui` feature)
+       kariuchi-solver gif [--out FILE] [--scale PX] [--delay CS] [--ply N] [--rollout N] [--no-odds] GAME_FILE
+                                         (with the `gif` feature)
 
-    let res = actions
-        .par_iter()
-        .map(|act| {
-            (0..tries)
-                .into_par_iter()
-                .map(|_| {
-                    let mut st = state.clone();
-                    st.act(*act);
-                    let p = 1. - evaluate::Evaluate::new(opt, st.clone()).evaluate();
-                    let mut solved = solved.lock().unwrap();
-                    *solved += 1;
-                    print!("\r{} / {}", solved, max);
-                    std::io::stdout().flush().unwrap();
-                    p
-                })
-                .collect()
-        })
-        .collect();
-    print!("\r");
-    res
-}
+Search statistics are logged after each CPU move; RUST_LOG=kariuchi_solver::stats=debug adds
+nodes per depth and time per candidate move, RUST_LOG=warn hides them.";
 
-fn read_int(max: usize) -> usize {
-    loop {
-        print!("> ");
-        use std::io::Write;
-        if let Err(e) = std::io::stdout().flush() {
-            println!("Error: {}", e);
-            continue;
-        }
-        let mut line = String::new();
-        if let Err(e) = std::io::stdin().read_line(&mut line) {
-            println!("Error: {}", e);
-            continue;
-        }
-        if line.trim() == "" && max == 1 {
-            println!("0 (forced)");
-            return 0;
-        }
-        let num = match line.trim().parse::<usize>() {
-            Ok(n) => n,
-            Err(e) => {
-                println!("Error: {}", e);
-                continue;
+type Args = (
+    Vec<String>,
+    tape::Tape,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut specs = vec![];
+    let mut tape = tape::Tape::random();
+    let mut save = None;
+    let mut save_game = None;
+    let mut timeline_csv = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--seed" => {
+                let seed = value()?.parse().map_err(|e| format!("--seed: {}", e))?;
+                tape = tape::Tape::seeded(seed);
             }
-        };
-        if num >= max {
-            println!("Error: out of range");
-            continue;
+            "--tape" => {
+                let path = value()?;
+                let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+                tape = tape::Tape::load(&text).map_err(|e| format!("{}: {}", path, e))?;
+            }
+            "--save-tape" => save = Some(value()?.clone()),
+            "--save-game" => save_game = Some(value()?.clone()),
+            "--timeline" => timeline_csv = Some(value()?.clone()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => specs.push(arg.clone()),
         }
-        return num;
     }
+    Ok((specs, tape, save, save_game, timeline_csv))
 }
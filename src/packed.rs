@@ -0,0 +1,215 @@
+//! `Packed`, a compact copy of a `State` with table-driven move generation for fast
+//! playouts.
+
+use std::sync::OnceLock;
+
+use crate::{
+    game::{Action, State, NUM_PIECES},
+    position::{Position, POSITIONS},
+    sticks::Sticks,
+};
+
+const BITS: usize = 6;
+const SIDE: usize = BITS * NUM_PIECES;
+const MASK: u64 = (1 << BITS) - 1;
+const SIDE_MASK: u64 = (1 << SIDE) - 1;
+const TURN: u64 = 1 << (2 * SIDE);
+const MAX_STICKS: usize = 5;
+
+/// A `State` packed into one `u64`: six bits per piece, the first side's four pieces in
+/// the low 24 bits and the second side's in the next 24, then the side to move. Pieces are
+/// kept sorted, so states that differ only in the order of a side's pieces pack the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Packed(u64);
+
+// Squares reached from each square with each throw, as `Position::advance` returns them.
+struct Moves {
+    to: [[[u8; 3]; MAX_STICKS + 1]; POSITIONS],
+    len: [[u8; MAX_STICKS + 1]; POSITIONS],
+}
+
+fn moves() -> &'static Moves {
+    static MOVES: OnceLock<Moves> = OnceLock::new();
+    MOVES.get_or_init(|| {
+        let mut m = Moves {
+            to: [[[0; 3]; MAX_STICKS + 1]; POSITIONS],
+            len: [[0; MAX_STICKS + 1]; POSITIONS],
+        };
+        for p in 0..POSITIONS {
+            for d in 1..=MAX_STICKS {
+                let to = Position::from(p).advance(d);
+                m.len[p][d] = to.len() as u8;
+                for (k, q) in to.into_iter().enumerate() {
+                    m.to[p][d][k] = usize::from(q) as u8;
+                }
+            }
+        }
+        m
+    })
+}
+
+fn goal() -> u8 {
+    usize::from(Position::goal()) as u8
+}
+
+fn pack(mut side: [u8; NUM_PIECES]) -> u64 {
+    side.sort_unstable();
+    side.iter()
+        .enumerate()
+        .fold(0, |acc, (i, p)| acc | (u64::from(*p) << (BITS * i)))
+}
+
+impl Packed {
+    fn side(&self, first: bool) -> [u8; NUM_PIECES] {
+        let bits = if first { self.0 } else { self.0 >> SIDE };
+        std::array::from_fn(|i| ((bits >> (BITS * i)) & MASK) as u8)
+    }
+    fn all_home(&self, first: bool) -> bool {
+        let home = pack([goal(); NUM_PIECES]);
+        let bits = if first { self.0 } else { self.0 >> SIDE };
+        bits & SIDE_MASK == home
+    }
+    /// The packed bits; equal states have equal keys.
+    pub fn key(&self) -> u64 {
+        self.0
+    }
+    /// Whether the first side is to move.
+    pub fn is_first(&self) -> bool {
+        self.0 & TURN != 0
+    }
+    /// Whether a side has all its pieces at the goal.
+    pub fn is_end(&self) -> bool {
+        self.all_home(true) || self.all_home(false)
+    }
+    /// Whether the first side won; only meaningful once `is_end`.
+    pub fn is_win_first(&self) -> bool {
+        self.all_home(true)
+    }
+    /// The pieces of one side in square order.
+    pub fn pieces(&self, first: bool) -> [Position; NUM_PIECES] {
+        self.side(first).map(|p| Position::from(usize::from(p)))
+    }
+    /// Every move the side to move can make with `sticks`, in the order of `State::actions`.
+    pub fn actions(&self, sticks: Sticks) -> Vec<Action> {
+        let mut v = Vec::with_capacity(16);
+        self.actions_into(sticks, &mut v);
+        v
+    }
+    /// Like `actions`, but reuses `out`, which is cleared first.
+    pub fn actions_into(&self, sticks: Sticks, out: &mut Vec<Action>) {
+        out.clear();
+        let d = usize::from(sticks);
+        let m = moves();
+        let mine = self.side(self.is_first());
+        let mut i = 0;
+        while i < NUM_PIECES {
+            let p = mine[i];
+            let mut count = 1;
+            while i + count < NUM_PIECES && mine[i + count] == p {
+                count += 1;
+            }
+            i += count;
+            if p == goal() {
+                continue;
+            }
+            let p = usize::from(p);
+            let movable = if p == usize::from(Position::start()) {
+                1
+            } else {
+                count
+            };
+            for k in 0..usize::from(m.len[p][d]) {
+                let to = Position::from(usize::from(m.to[p][d][k]));
+                for num in 1..=movable {
+                    out.push(Action::new(Position::from(p), to, num, sticks));
+                }
+            }
+        }
+    }
+    /// Applies a move of the side to move, like `State::act`.
+    pub fn act(&mut self, act: Action) {
+        let first = self.is_first();
+        let at = usize::from(act.at()) as u8;
+        let to = usize::from(act.to()) as u8;
+        let mut mine = self.side(first);
+        let mut left = act.num();
+        for p in mine.iter_mut() {
+            if left > 0 && *p == at {
+                *p = to;
+                left -= 1;
+            }
+        }
+        assert_eq!(left, 0, "not enough pieces on {}", act.at());
+        let mut theirs = self.side(!first);
+        if to != goal() {
+            for p in theirs.iter_mut() {
+                if *p == to {
+                    *p = usize::from(Position::start()) as u8;
+                }
+            }
+        }
+        let (a, b) = if first {
+            (mine, theirs)
+        } else {
+            (theirs, mine)
+        };
+        let next_first = if act.sticks().replay() { first } else { !first };
+        let turn = if next_first { TURN } else { 0 };
+        self.0 = pack(a) | (pack(b) << SIDE) | turn;
+    }
+}
+
+impl From<&State> for Packed {
+    fn from(s: &State) -> Self {
+        let side = |first| s.pieces(first).map(|p| usize::from(p) as u8);
+        let turn = if s.is_first() { TURN } else { 0 };
+        Packed(pack(side(true)) | (pack(side(false)) << SIDE) | turn)
+    }
+}
+
+impl From<Packed> for State {
+    /// The pieces come back in square order.
+    fn from(p: Packed) -> Self {
+        State::from_pieces(p.pieces(true), p.pieces(false), p.is_first())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tape::Tape;
+    #[test]
+    fn size() {
+        println!(
+            "State: {} bytes, Packed: {} bytes",
+            std::mem::size_of::<State>(),
+            std::mem::size_of::<Packed>()
+        );
+        assert_eq!(std::mem::size_of::<Packed>(), 8);
+    }
+    #[test]
+    fn same_as_state() {
+        for seed in 0..20 {
+            let mut tape = Tape::seeded(seed);
+            let mut s = State::new();
+            let mut p = Packed::from(&s);
+            let mut k = seed as usize;
+            while !s.is_end() {
+                let sticks = tape.throw().unwrap();
+                let actions = s.actions(sticks);
+                assert_eq!(p.actions(sticks), actions);
+                let act = actions[k % actions.len()];
+                k = (k * 7 + 3) % 1009;
+                s.act(act);
+                p.act(act);
+                assert_eq!(p, Packed::from(&s));
+                assert_eq!(p.is_end(), s.is_end());
+                assert_eq!(p.is_first(), s.is_first());
+            }
+            assert_eq!(p.is_win_first(), s.is_win_first());
+            let back = State::from(p);
+            assert_eq!(Packed::from(&back), p);
+            assert!(back.is_end());
+        }
+    }
+}
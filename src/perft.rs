@@ -0,0 +1,145 @@
+//! Move-path enumeration ("perft") for checking the rules against known counts.
+//!
+//! One ply is a throw followed by a move; every ply branches over all five throws and
+//! every legal move for each. Finished games are not expanded further.
+
+use std::ops::AddAssign;
+
+use crate::{error::KariuchiError, game::State, position::Position, sticks::Sticks};
+
+/// What the moves of the last ply did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    /// Positions reached.
+    pub nodes: u64,
+    /// Moves that sent opposing pieces back to the start.
+    pub captures: u64,
+    /// Moves that brought pieces to the goal.
+    pub goals: u64,
+    /// Moves after which the same side moves again.
+    pub replays: u64,
+    /// Moves that won the game.
+    pub wins: u64,
+}
+
+impl AddAssign for Counts {
+    fn add_assign(&mut self, o: Self) {
+        self.nodes += o.nodes;
+        self.captures += o.captures;
+        self.goals += o.goals;
+        self.replays += o.replays;
+        self.wins += o.wins;
+    }
+}
+
+/// Counts the move paths `depth` plies deep from `state`.
+pub fn perft(state: &State, depth: usize) -> Counts {
+    divide(state, depth)
+        .into_iter()
+        .map(|(_, c)| c)
+        .fold(Counts::default(), |mut acc, c| {
+            acc += c;
+            acc
+        })
+}
+
+/// `perft` split by the first throw, in the order of `Sticks::all_sticks`.
+pub fn divide(state: &State, depth: usize) -> Vec<(Sticks, Counts)> {
+    Sticks::all_sticks()
+        .into_iter()
+        .map(|(_, sticks)| {
+            let mut c = Counts::default();
+            if depth > 0 && !state.is_end() {
+                for act in state.actions(sticks) {
+                    if depth == 1 {
+                        let d = state.describe(act);
+                        c.nodes += 1;
+                        c.captures += u64::from(d.captured > 0);
+                        c.goals += u64::from(act.to() == Position::goal());
+                        c.replays += u64::from(!d.passes_turn && !d.wins);
+                        c.wins += u64::from(d.wins);
+                    } else {
+                        let mut next = state.clone();
+                        next.act(act);
+                        c += perft(&next, depth - 1);
+                    }
+                }
+            }
+            (sticks, c)
+        })
+        .collect()
+}
+
+const USAGE: &str = "usage: kariuchi-solver perft [--depth N] [--divide] [POSITION_FILE]";
+
+/// The `perft` subcommand: counts move paths to `--depth` from POSITION_FILE, or from the
+/// start of the game.
+pub fn main(args: &[String]) -> Result<(), String> {
+    let mut depth = 3;
+    let mut split = false;
+    let mut input = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--depth" => {
+                let v = it.next().ok_or("--depth needs a value")?;
+                depth = v.parse().map_err(|e| format!("--depth: {}", e))?;
+            }
+            "--divide" => split = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}\n{}", arg, USAGE)),
+            _ => input = Some(arg.clone()),
+        }
+    }
+    // Without a file, count from the start of the game.
+    let state: State = match input {
+        Some(path) => std::fs::read_to_string(&path)
+            .map_err(|e| format!("{}: {}", path, e))?
+            .parse()
+            .map_err(|e: KariuchiError| format!("{}: {}", path, e))?,
+        None => State::new(),
+    };
+    println!(
+        "{:<8}{:>14}{:>12}{:>12}{:>12}{:>12}",
+        "depth", "nodes", "captures", "goals", "replays", "wins"
+    );
+    let row = |label: String, c: Counts| {
+        println!(
+            "{:<8}{:>14}{:>12}{:>12}{:>12}{:>12}",
+            label, c.nodes, c.captures, c.goals, c.replays, c.wins
+        )
+    };
+    for d in 1..=depth {
+        row(d.to_string(), perft(&state, d));
+    }
+    if split && depth > 0 {
+        let mut parts = divide(&state, depth);
+        parts.sort_by_key(|(s, _)| *s);
+        for (sticks, c) in parts {
+            row(format!("  {}", usize::from(sticks)), c);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn shallow() {
+        // From the start only one piece may leave, so every throw has one move.
+        let c = perft(&State::new(), 1);
+        assert_eq!(
+            c,
+            Counts {
+                nodes: 5,
+                captures: 0,
+                goals: 0,
+                replays: 2,
+                wins: 0,
+            }
+        );
+        let parts = divide(&State::new(), 2);
+        let total: u64 = parts.iter().map(|(_, c)| c.nodes).sum();
+        assert_eq!(total, perft(&State::new(), 2).nodes);
+    }
+}
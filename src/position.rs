@@ -1,4 +1,13 @@
-use std::fmt::{self, Display, Formatter};
+//! The squares of the board: the start, the outer route, the shortcuts through the
+//! center, and the goal.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+use crate::error::KariuchiError;
 
 const S_ON_START: usize = 0;
 const ON_START: usize = 1;
@@ -10,10 +19,12 @@ const S_ON_GOAL: usize = S_ON_ROUTES + ON_ROUTES;
 const ON_GOAL: usize = 1;
 const S_ON_SHORTCUTS: usize = S_ON_GOAL + ON_GOAL;
 const NUM_SHORTCUTS: usize = 6;
+/// Shortcuts on each half of the board.
 pub const OPP_SHORTCUTS: usize = NUM_SHORTCUTS / 2;
 const ON_SHORTCUT: usize = 3;
 const S_ON_CENTER: usize = S_ON_SHORTCUTS + NUM_SHORTCUTS * ON_SHORTCUT;
 const ON_CENTER: usize = 1;
+/// Number of distinct positions; `Position::from` accepts `0..POSITIONS`.
 pub const POSITIONS: usize = S_ON_CENTER + ON_CENTER;
 
 /*
@@ -35,35 +46,72 @@ pub const POSITIONS: usize = S_ON_CENTER + ON_CENTER;
 15 -> c -> C -> f -> 30
 */
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// A square of the board, or the start and goal where pieces wait before and after the race.
+///
+/// Displayed and parsed as `S`, `R1`..`R30`, `G`, `C` and `K{path}_{step}`
+/// (see the README for the layout).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Position(usize);
+/// The structured view of a `Position`: `ROUTE(x)` is the x-th square of the outer route
+/// and `SHORTCUT(path, x)` the x-th square of shortcut `path` (0..3 lead to the center).
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-enum HumanPosition {
+pub enum HumanPosition {
+    /// `S`, where pieces wait to enter.
     START,
+    /// `R1`..`R30`.
     ROUTE(usize),
+    /// `G`, where pieces finish.
     GOAL,
+    /// `C`, where the shortcuts cross.
     CENTER,
+    /// `K{path}_{step}`.
     SHORTCUT(usize, usize),
 }
 
 impl Position {
+    /// The square with index `x`, or an error if there is none.
+    pub fn checked(x: usize) -> Result<Self, KariuchiError> {
+        if x < POSITIONS {
+            Ok(Position(x))
+        } else {
+            Err(KariuchiError::NoSuchPosition(x))
+        }
+    }
+    /// `S`.
     pub fn start() -> Self {
         Position(S_ON_START)
     }
-    fn route(x: usize) -> Self {
-        assert!(S_ON_START <= x && x < S_ON_GOAL);
-        Position(x)
+    /// Route square `x`, counting the start as 0. Panics where `try_route` returns an error.
+    pub fn route(x: usize) -> Self {
+        Self::try_route(x).unwrap_or_else(|e| panic!("{}", e))
     }
+    /// Route square `x`, or an error unless `x` is below 31.
+    pub fn try_route(x: usize) -> Result<Self, KariuchiError> {
+        if x < S_ON_GOAL {
+            Ok(Position(x))
+        } else {
+            Err(KariuchiError::NoSuchRoute(x))
+        }
+    }
+    /// `G`.
     pub fn goal() -> Self {
         Position(S_ON_GOAL)
     }
-    fn center() -> Self {
+    /// `C`.
+    pub fn center() -> Self {
         Position(S_ON_CENTER)
     }
-    fn shortcut(path: usize, x: usize) -> Self {
-        assert!(path < NUM_SHORTCUTS);
-        assert!(x < ON_SHORTCUT);
-        Position(S_ON_SHORTCUTS + path * ON_SHORTCUT + x)
+    /// Step `x` of shortcut `path`. Panics where `try_shortcut` returns an error.
+    pub fn shortcut(path: usize, x: usize) -> Self {
+        Self::try_shortcut(path, x).unwrap_or_else(|e| panic!("{}", e))
+    }
+    /// Step `x` of shortcut `path`, or an error unless `path` is below 6 and `x` below 3.
+    pub fn try_shortcut(path: usize, x: usize) -> Result<Self, KariuchiError> {
+        if path < NUM_SHORTCUTS && x < ON_SHORTCUT {
+            Ok(Position(S_ON_SHORTCUTS + path * ON_SHORTCUT + x))
+        } else {
+            Err(KariuchiError::NoSuchShortcut { path, x })
+        }
     }
     fn on_route(x: usize) -> Self {
         if x >= S_ON_GOAL {
@@ -101,6 +149,8 @@ impl Position {
             }
         }
     }
+    /// Squares reached by moving `d` steps; more than one when a shortcut or the center
+    /// gives a choice, none from the goal.
     pub fn advance(self, d: usize) -> Vec<Position> {
         match self.into() {
             HumanPosition::START => {
@@ -130,15 +180,37 @@ impl Position {
             }
         }
     }
+    /// Fewest single steps needed to reach the goal, taking shortcuts when they help.
+    pub fn steps_to_goal(self) -> usize {
+        static STEPS: OnceLock<[usize; POSITIONS]> = OnceLock::new();
+        STEPS.get_or_init(|| {
+            let mut dist = [usize::MAX; POSITIONS];
+            dist[S_ON_GOAL] = 0;
+            let mut changed = true;
+            while changed {
+                changed = false;
+                for i in 0..POSITIONS {
+                    for next in Position(i).advance(1) {
+                        let d = dist[next.0].saturating_add(1);
+                        if d < dist[i] {
+                            dist[i] = d;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+            dist
+        })[self.0]
+    }
 }
 impl From<Position> for usize {
     fn from(x: Position) -> Self {
         x.0
     }
 }
-impl Into<HumanPosition> for Position {
-    fn into(self) -> HumanPosition {
-        let x = self.0;
+impl From<Position> for HumanPosition {
+    fn from(p: Position) -> HumanPosition {
+        let x = p.0;
         if x == S_ON_START {
             HumanPosition::START
         } else if x == S_ON_GOAL {
@@ -155,9 +227,36 @@ impl Into<HumanPosition> for Position {
     }
 }
 impl From<usize> for Position {
+    /// Panics if `x` is not below `POSITIONS`; see `Position::checked`.
     fn from(x: usize) -> Self {
-        assert! {x < POSITIONS}
-        Position(x)
+        Position::checked(x).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+impl FromStr for Position {
+    type Err = KariuchiError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || KariuchiError::BadPosition(s.to_string());
+        let num = |x: &str| x.parse::<usize>().map_err(|_| bad());
+        match s {
+            "S" => Ok(Position::start()),
+            "G" => Ok(Position::goal()),
+            "C" => Ok(Position::center()),
+            _ => {
+                if let Some(x) = s.strip_prefix('R') {
+                    let x = num(x)?;
+                    if x < S_ON_ROUTES {
+                        return Err(bad());
+                    }
+                    Position::try_route(x).map_err(|_| bad())
+                } else if let Some(k) = s.strip_prefix('K') {
+                    let (path, x) = k.split_once('_').ok_or_else(bad)?;
+                    Position::try_shortcut(num(path)?, num(x)?).map_err(|_| bad())
+                } else {
+                    Err(bad())
+                }
+            }
+        }
     }
 }
 
@@ -173,6 +272,8 @@ impl Display for Position {
     }
 }
 
+/// Text layout of the board used by `State::visual`: height, width and the
+/// (row, column) of every position.
 pub fn alignment() -> (usize, usize, Vec<(usize, usize)>) {
     let mut a = vec![(0, 0); POSITIONS];
     let cw = 3;
@@ -205,6 +306,51 @@ pub fn alignment() -> (usize, usize, Vec<(usize, usize)>) {
     (h, w, a)
 }
 
+/// Board geometry for drawing: the (x, y) center of every position in units of one square,
+/// taken from `alignment`. The start sits before `R1` and the goal after `R30`, and the
+/// center is midway between the shortcuts.
+pub fn coordinates() -> Vec<(f64, f64)> {
+    let (h, _, a) = alignment();
+    let cw = 3.;
+    let mut c: Vec<(f64, f64)> = a.iter().map(|(y, x)| (*x as f64 / cw, *y as f64)).collect();
+    c[S_ON_START] = (c[S_ON_ROUTES].0 - 1.5, 0.);
+    c[S_ON_GOAL] = (c[S_ON_GOAL - 1].0 - 1.5, (h - 1) as f64);
+    let first = c[S_ON_SHORTCUTS].0;
+    let last = c[S_ON_SHORTCUTS + (OPP_SHORTCUTS - 1) * ON_SHORTCUT].0;
+    c[S_ON_CENTER].0 = (first + last) / 2.;
+    c
+}
+
+/// The connections between neighbouring positions, for drawing the board: the route from
+/// the start to the goal, and each shortcut between the route and the center.
+pub fn edges() -> Vec<(Position, Position)> {
+    let mut e = vec![];
+    for x in S_ON_START..S_ON_GOAL {
+        e.push((Position(x), Position(x + 1)));
+    }
+    for p in 0..NUM_SHORTCUTS {
+        let mut chain = vec![];
+        let corner = Position::route((p + 1) * ON_CURVE);
+        if p < OPP_SHORTCUTS {
+            chain.push(corner);
+        } else {
+            chain.push(Position::center());
+        }
+        for i in 0..ON_SHORTCUT {
+            chain.push(Position::shortcut(p, i));
+        }
+        if p < OPP_SHORTCUTS {
+            chain.push(Position::center());
+        } else {
+            chain.push(corner);
+        }
+        for w in chain.windows(2) {
+            e.push((w[0], w[1]));
+        }
+    }
+    e
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -215,6 +361,19 @@ mod test {
         }
     }
     #[test]
+    fn parse_positions() {
+        for i in 0..POSITIONS {
+            let p = Position::from(i);
+            assert_eq!(format!("{}", p).parse::<Position>(), Ok(p));
+        }
+        for bad in ["", "R0", "R31", "K6_0", "K0_3", "K1", "X", "R-1"] {
+            assert_eq!(
+                bad.parse::<Position>(),
+                Err(KariuchiError::BadPosition(bad.to_string()))
+            );
+        }
+    }
+    #[test]
     fn advance_one() {
         for i in 0..POSITIONS {
             let p = Position::from(i);
@@ -227,6 +386,60 @@ mod test {
         }
     }
     #[test]
+    fn checked() {
+        assert_eq!(Position::checked(0), Ok(Position::start()));
+        assert_eq!(
+            Position::checked(POSITIONS),
+            Err(KariuchiError::NoSuchPosition(POSITIONS))
+        );
+        assert_eq!(
+            Position::try_route(S_ON_GOAL - 1),
+            Ok(Position(S_ON_GOAL - 1))
+        );
+        assert_eq!(
+            Position::try_route(S_ON_GOAL),
+            Err(KariuchiError::NoSuchRoute(S_ON_GOAL))
+        );
+        assert!(Position::try_shortcut(NUM_SHORTCUTS - 1, ON_SHORTCUT - 1).is_ok());
+        assert_eq!(
+            Position::try_shortcut(NUM_SHORTCUTS, 0),
+            Err(KariuchiError::NoSuchShortcut {
+                path: NUM_SHORTCUTS,
+                x: 0
+            })
+        );
+        assert!(Position::try_shortcut(0, ON_SHORTCUT).is_err());
+    }
+    #[test]
+    fn steps_to_goal() {
+        assert_eq!(Position::goal().steps_to_goal(), 0);
+        assert_eq!(Position::route(S_ON_GOAL - 1).steps_to_goal(), 1);
+        assert_eq!(Position::center().steps_to_goal(), ON_SHORTCUT + 1);
+        for i in 0..POSITIONS {
+            println!(
+                "{}: {}",
+                Position::from(i),
+                Position::from(i).steps_to_goal()
+            );
+        }
+    }
+    #[test]
+    fn geometry() {
+        let c = coordinates();
+        assert_eq!(c.len(), POSITIONS);
+        for (a, b) in edges() {
+            let (ax, ay) = c[usize::from(a)];
+            let (bx, by) = c[usize::from(b)];
+            let d = ((ax - bx).powi(2) + (ay - by).powi(2)).sqrt();
+            println!("{} -> {}: {:.2}", a, b, d);
+            assert!(d >= 1.);
+        }
+        assert_eq!(
+            edges().len(),
+            ON_ROUTES + 1 + NUM_SHORTCUTS * (ON_SHORTCUT + 1)
+        );
+    }
+    #[test]
     fn advance_five() {
         for i in 0..POSITIONS {
             let p = Position::from(i);
@@ -0,0 +1,126 @@
+//! `Record`, a whole game kept as its starting position and moves, with a plain text
+//! format to save and load it.
+
+use crate::{
+    error::KariuchiError,
+    game::{Action, State},
+    position::Position,
+    sticks::Sticks,
+};
+
+/// A game as played: the starting position and every move applied to it with `State::act`.
+/// Each `Action` carries the throw it was played with.
+///
+/// `save` writes the position as `State` displays it, then one move per line as
+/// `STICKS AT TO NUM`, e.g. `3 S R3 1`; `load` reads it back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// The position before the first move.
+    pub start: State,
+    /// The moves in the order they were played.
+    pub moves: Vec<Action>,
+}
+
+impl Record {
+    /// A game from `start` without moves yet.
+    pub fn new(start: State) -> Self {
+        Record {
+            start,
+            moves: vec![],
+        }
+    }
+    /// Appends `act` without checking it; `states` does.
+    pub fn push(&mut self, act: Action) {
+        self.moves.push(act);
+    }
+    /// The position before every move followed by the final one, or an error naming the
+    /// first move that is not legal where it was played.
+    pub fn states(&self) -> Result<Vec<State>, KariuchiError> {
+        let mut state = self.start.clone();
+        let mut v = vec![state.clone()];
+        for (i, act) in self.moves.iter().enumerate() {
+            state
+                .try_act(*act)
+                .map_err(|e| KariuchiError::AtMove(i + 1, Box::new(e)))?;
+            v.push(state.clone());
+        }
+        Ok(v)
+    }
+    /// The game in the text format above.
+    pub fn save(&self) -> String {
+        let mut s = self.start.to_string();
+        for act in &self.moves {
+            s += &format!(
+                "{} {} {} {}\n",
+                usize::from(act.sticks()),
+                act.at(),
+                act.to(),
+                act.num()
+            );
+        }
+        s
+    }
+    /// Reads a game written by `save`, failing on bad text or an illegal move.
+    pub fn load(text: &str) -> Result<Self, KariuchiError> {
+        let mut lines = text.lines().map(|l| l.trim()).filter(|l| !l.is_empty());
+        let head: Vec<&str> = lines.by_ref().take(2).collect();
+        let start: State = head.join("\n").parse()?;
+        let mut record = Record::new(start);
+        for line in lines {
+            let bad = |error: KariuchiError| KariuchiError::BadMove {
+                line: line.to_string(),
+                error: Box::new(error),
+            };
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let [sticks, at, to, num] = words[..] else {
+                return Err(bad(KariuchiError::Syntax(
+                    "expected `STICKS AT TO NUM`".to_string(),
+                )));
+            };
+            let sticks = sticks
+                .parse::<usize>()
+                .map_err(|_| KariuchiError::BadThrow(sticks.to_string()))
+                .and_then(Sticks::checked)
+                .map_err(bad)?;
+            let at: Position = at.parse().map_err(bad)?;
+            let to: Position = to.parse().map_err(bad)?;
+            let num: usize = num
+                .parse()
+                .map_err(|_| bad(KariuchiError::Syntax(format!("bad count `{}`", num))))?;
+            record.push(Action::try_new(at, to, num, sticks).map_err(bad)?);
+        }
+        record.states()?;
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tape::Tape;
+    #[test]
+    fn save_load() {
+        let mut tape = Tape::seeded(11);
+        let mut state = State::new();
+        let mut record = Record::new(state.clone());
+        while !state.is_end() {
+            let act = state.actions(tape.throw().unwrap())[0];
+            record.push(act);
+            state.act(act);
+        }
+        let text = record.save();
+        println!("{}", text);
+        let loaded = Record::load(&text).unwrap();
+        assert_eq!(loaded, record);
+        assert_eq!(*loaded.states().unwrap().last().unwrap(), state);
+        let e = Record::load(&(text.clone() + "1 S R1 1\n")).unwrap_err();
+        println!("{}", e);
+        assert!(matches!(e, KariuchiError::AtMove(..)));
+        let start = State::new().to_string();
+        assert!(Record::load(&(start.clone() + "3 S R2 1\n")).is_err());
+        let e = Record::load(&(start.clone() + "6 S R6 1\n")).unwrap_err();
+        println!("{}", e);
+        assert!(matches!(e, KariuchiError::BadMove { .. }));
+        assert!(Record::load(&(start + "3 S R3\n")).is_err());
+    }
+}
@@ -0,0 +1,501 @@
+//! Animated GIF replays of a `Record`, enabled by the `gif` feature.
+//!
+//! Each move gets a frame of the position before it with the throw, an arrow along the
+//! move and a bar with the first side's win probability. Captures add a few short frames
+//! flashing the captured square and the start. The board is laid out like `svg` and
+//! rasterized here, so no font or image library is needed.
+
+use rayon::prelude::*;
+
+use crate::{
+    evaluate::{Evaluate, Opt},
+    game::State,
+    position::{self, HumanPosition, Position, POSITIONS},
+    record::Record,
+    svg,
+};
+
+// Palette indices.
+const BACKGROUND: u8 = 0;
+const EDGE: u8 = 1;
+const SQUARE: u8 = 2;
+const OUTLINE: u8 = 3;
+const FIRST: u8 = 4;
+const SECOND: u8 = 5;
+const ARROW: u8 = 6;
+const WHITE: u8 = 7;
+const FLASH: u8 = 8;
+
+const PALETTE: [u8; 27] = [
+    0xf4, 0xec, 0xd8, // background
+    0x88, 0x88, 0x66, // edge
+    0xff, 0xf8, 0xe8, // square
+    0x55, 0x55, 0x33, // outline
+    0xdd, 0x33, 0x33, // first
+    0x33, 0x66, 0xcc, // second
+    0x22, 0xaa, 0x22, // arrow
+    0xff, 0xff, 0xff, // white
+    0xff, 0xcc, 0x00, // flash
+];
+
+// 3x5 digits, one row of three bits per byte.
+const DIGITS: [[u8; 5]; 10] = [
+    [7, 5, 5, 5, 7],
+    [2, 6, 2, 2, 7],
+    [7, 1, 7, 4, 7],
+    [7, 1, 3, 1, 7],
+    [5, 5, 7, 1, 1],
+    [7, 4, 7, 1, 7],
+    [7, 4, 7, 5, 7],
+    [7, 1, 1, 2, 2],
+    [7, 5, 7, 5, 7],
+    [7, 5, 7, 1, 7],
+];
+
+/// Largest `GifOpt::scale`; every frame is a full copy of the board, so this bounds memory.
+pub const MAX_SCALE: u16 = 96;
+
+/// Rendering settings for `render`.
+#[derive(Debug, Clone)]
+pub struct GifOpt {
+    /// Pixels per square, at most `MAX_SCALE`.
+    pub scale: u16,
+    /// Time each move is shown, in hundredths of a second.
+    pub delay: u16,
+    /// Search used for the win probability bar; `None` leaves the bar out.
+    pub eval: Option<Opt>,
+}
+
+impl Default for GifOpt {
+    fn default() -> Self {
+        GifOpt {
+            scale: 24,
+            delay: 100,
+            eval: Some(Opt {
+                ply: 1,
+                rollout: 20,
+            }),
+        }
+    }
+}
+
+/// Encodes the replay of `record` as an animated GIF that loops forever.
+pub fn render(record: &Record, opt: &GifOpt) -> Result<Vec<u8>, String> {
+    let states = record.states().map_err(|e| e.to_string())?;
+    let raster = Raster::new(opt.scale, opt.eval.is_some())?;
+    // Probability that the first side wins, before every move and at the end.
+    let odds: Option<Vec<f64>> = opt.eval.map(|eval| {
+        states
+            .par_iter()
+            .map(|s| {
+                let p = Evaluate::new(eval, s.clone()).evaluate();
+                match (s.is_end(), s.is_first()) {
+                    (true, _) => f64::from(u8::from(s.is_win_first())),
+                    (false, true) => p,
+                    (false, false) => 1. - p,
+                }
+            })
+            .collect()
+    });
+    let (w, h) = (raster.width, raster.height);
+    let mut out = vec![];
+    {
+        let mut encoder = gif::Encoder::new(&mut out, w, h, &PALETTE).map_err(|e| e.to_string())?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(|e| e.to_string())?;
+        let mut write = |pixels: Vec<u8>, delay: u16| {
+            let mut frame = gif::Frame::from_indexed_pixels(w, h, pixels, None);
+            frame.delay = delay;
+            encoder.write_frame(&frame).map_err(|e| e.to_string())
+        };
+        let odds = |i: usize| odds.as_ref().map(|v| v[i]);
+        for (i, act) in record.moves.iter().enumerate() {
+            let before = &states[i];
+            let mut r = raster.clone();
+            r.board(before);
+            r.arrow(&svg::path(act));
+            r.throw(usize::from(act.sticks()), before.is_first());
+            r.bar(odds(i));
+            write(r.pixels, opt.delay)?;
+            let captured = before.describe(*act).captured;
+            if captured > 0 {
+                for k in 0..4 {
+                    let mut r = raster.clone();
+                    r.board(&states[i + 1]);
+                    if k % 2 == 0 {
+                        r.flash(act.to());
+                        r.flash(Position::start());
+                    }
+                    r.bar(odds(i + 1));
+                    write(r.pixels, (opt.delay / 4).max(1))?;
+                }
+            }
+        }
+        let mut r = raster.clone();
+        let last = states.len() - 1;
+        r.board(&states[last]);
+        r.bar(odds(last));
+        write(r.pixels, opt.delay * 3)?;
+    }
+    Ok(out)
+}
+
+/// The `gif` subcommand: renders the game in GAME_FILE to `--out`.
+pub fn main(args: &[String]) -> Result<(), String> {
+    let mut opt = GifOpt::default();
+    let mut eval = opt.eval.unwrap();
+    let mut odds = true;
+    let mut out = "replay.gif".to_string();
+    let mut input = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or_else(|| format!("{} needs a value", arg));
+        let num = |v: &String| v.parse::<u16>().map_err(|e| format!("{}: {}", arg, e));
+        match arg.as_str() {
+            "--out" => out = value()?.clone(),
+            "--scale" => opt.scale = num(value()?)?.max(4),
+            "--delay" => opt.delay = num(value()?)?,
+            "--ply" => eval.ply = num(value()?)?.into(),
+            "--rollout" => eval.rollout = num(value()?)?.max(1).into(),
+            "--no-odds" => odds = false,
+            _ if arg.starts_with("--") => {
+                return Err(format!(
+                    "unknown option {}\nusage: kariuchi-solver gif [--out FILE] [--scale PX] \
+                     [--delay CS] [--ply N] [--rollout N] [--no-odds] GAME_FILE",
+                    arg
+                ))
+            }
+            _ => input = Some(arg.clone()),
+        }
+    }
+    opt.eval = odds.then_some(eval);
+    let path = input.ok_or("missing GAME_FILE (written by --save-game)")?;
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+    let record = Record::load(&text).map_err(|e| format!("{}: {}", path, e))?;
+    let bytes = render(&record, &opt)?;
+    std::fs::write(&out, bytes).map_err(|e| format!("{}: {}", out, e))
+}
+
+// An indexed-color frame with the board geometry scaled to pixels.
+#[derive(Clone)]
+struct Raster {
+    scale: f64,
+    width: u16,
+    height: u16,
+    coords: Vec<(f64, f64)>,
+    pixels: Vec<u8>,
+}
+
+impl Raster {
+    /// Fails when the scale is above `MAX_SCALE`.
+    fn new(scale: u16, bar: bool) -> Result<Self, String> {
+        if scale > MAX_SCALE {
+            return Err(format!(
+                "scale {} is above the maximum {}",
+                scale, MAX_SCALE
+            ));
+        }
+        let s = f64::from(scale);
+        let coords: Vec<(f64, f64)> = position::coordinates()
+            .into_iter()
+            .map(|(x, y)| (x * s + 2.5 * s, y * s + 2. * s))
+            .collect();
+        let w = coords.iter().map(|c| c.0).fold(0., f64::max) + 2.5 * s;
+        let h = coords.iter().map(|c| c.1).fold(0., f64::max) + if bar { 2. } else { 1. } * s;
+        let (width, height) = (w.ceil() as u16, h.ceil() as u16);
+        let mut r = Raster {
+            scale: s,
+            width,
+            height,
+            coords,
+            pixels: vec![BACKGROUND; usize::from(width) * usize::from(height)],
+        };
+        r.edges();
+        r.squares();
+        Ok(r)
+    }
+    fn at(&self, p: Position) -> (f64, f64) {
+        self.coords[usize::from(p)]
+    }
+    fn set(&mut self, x: isize, y: isize, color: u8) {
+        let (w, h) = (usize::from(self.width), usize::from(self.height));
+        if x >= 0 && y >= 0 && (x as usize) < w && (y as usize) < h {
+            self.pixels[y as usize * w + x as usize] = color;
+        }
+    }
+    // Calls `f` for every pixel center inside the bounding box, setting those it accepts.
+    fn fill(
+        &mut self,
+        (x0, y0): (f64, f64),
+        (x1, y1): (f64, f64),
+        color: u8,
+        f: impl Fn(f64, f64) -> bool,
+    ) {
+        for y in y0.floor() as isize..=y1.ceil() as isize {
+            for x in x0.floor() as isize..=x1.ceil() as isize {
+                if f(x as f64 + 0.5, y as f64 + 0.5) {
+                    self.set(x, y, color);
+                }
+            }
+        }
+    }
+    fn rect(&mut self, (x, y): (f64, f64), w: f64, h: f64, color: u8) {
+        self.fill((x, y), (x + w, y + h), color, |px, py| {
+            px >= x && px < x + w && py >= y && py < y + h
+        });
+    }
+    fn circle(&mut self, (x, y): (f64, f64), r: f64, color: u8) {
+        self.fill((x - r, y - r), (x + r, y + r), color, |px, py| {
+            (px - x).powi(2) + (py - y).powi(2) <= r * r
+        });
+    }
+    fn ring(&mut self, c: (f64, f64), r: f64, width: f64, color: u8) {
+        let inner = r - width;
+        self.fill((c.0 - r, c.1 - r), (c.0 + r, c.1 + r), color, |px, py| {
+            let d = (px - c.0).powi(2) + (py - c.1).powi(2);
+            d <= r * r && d >= inner * inner
+        });
+    }
+    fn line(&mut self, a: (f64, f64), b: (f64, f64), width: f64, color: u8) {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let len2 = (dx * dx + dy * dy).max(1e-9);
+        let r = width / 2.;
+        let lo = (a.0.min(b.0) - r, a.1.min(b.1) - r);
+        let hi = (a.0.max(b.0) + r, a.1.max(b.1) + r);
+        self.fill(lo, hi, color, |px, py| {
+            let t = (((px - a.0) * dx + (py - a.1) * dy) / len2).clamp(0., 1.);
+            let (qx, qy) = (a.0 + t * dx - px, a.1 + t * dy - py);
+            qx * qx + qy * qy <= r * r
+        });
+    }
+    fn triangle(&mut self, p: [(f64, f64); 3], color: u8) {
+        let lo = (
+            p.iter().map(|q| q.0).fold(f64::MAX, f64::min),
+            p.iter().map(|q| q.1).fold(f64::MAX, f64::min),
+        );
+        let hi = (
+            p.iter().map(|q| q.0).fold(f64::MIN, f64::max),
+            p.iter().map(|q| q.1).fold(f64::MIN, f64::max),
+        );
+        let side = |a: (f64, f64), b: (f64, f64), x: f64, y: f64| {
+            (b.0 - a.0) * (y - a.1) - (b.1 - a.1) * (x - a.0)
+        };
+        self.fill(lo, hi, color, |x, y| {
+            let d = [
+                side(p[0], p[1], x, y),
+                side(p[1], p[2], x, y),
+                side(p[2], p[0], x, y),
+            ];
+            d.iter().all(|v| *v >= 0.) || d.iter().all(|v| *v <= 0.)
+        });
+    }
+    // `n` in the 3x5 font, each font pixel `size` wide, centered on `c`.
+    fn number(&mut self, c: (f64, f64), n: usize, size: f64, color: u8) {
+        let text = n.to_string();
+        let w = (text.len() * 4 - 1) as f64 * size;
+        let (x0, y0) = (c.0 - w / 2., c.1 - 2.5 * size);
+        for (k, d) in text.bytes().enumerate() {
+            let glyph = DIGITS[usize::from(d - b'0')];
+            for (row, bits) in glyph.iter().enumerate() {
+                for col in 0..3 {
+                    if bits & (4 >> col) != 0 {
+                        let x = x0 + (k * 4 + col) as f64 * size;
+                        self.rect((x, y0 + row as f64 * size), size, size, color);
+                    }
+                }
+            }
+        }
+    }
+    fn edges(&mut self) {
+        let s = self.scale;
+        for (a, b) in position::edges() {
+            let (pa, pb) = (self.at(a), self.at(b));
+            let on_route = |p: Position| matches!(p.into(), HumanPosition::ROUTE(_));
+            if on_route(a) && on_route(b) && pa.1 != pb.1 {
+                // The route turns around the right end of the board, as in `svg`.
+                let r = 1.5 * s;
+                let ctrl = [pa, (pa.0 + r, pa.1), (pb.0 + r, pb.1), pb];
+                let mut prev = pa;
+                for k in 1..=24 {
+                    let t = k as f64 / 24.;
+                    let u = 1. - t;
+                    let w = [u * u * u, 3. * u * u * t, 3. * u * t * t, t * t * t];
+                    let q = (
+                        (0..4).map(|i| w[i] * ctrl[i].0).sum(),
+                        (0..4).map(|i| w[i] * ctrl[i].1).sum(),
+                    );
+                    self.line(prev, q, s * 0.1, EDGE);
+                    prev = q;
+                }
+            } else {
+                self.line(pa, pb, s * 0.1, EDGE);
+            }
+        }
+    }
+    fn squares(&mut self) {
+        let s = self.scale;
+        for i in 0..POSITIONS {
+            let p = Position::from(i);
+            let c = self.at(p);
+            if p == Position::start() || p == Position::goal() {
+                self.rect((c.0 - 0.6 * s, c.1 - 0.9 * s), 1.2 * s, 1.8 * s, OUTLINE);
+                self.rect((c.0 - 0.55 * s, c.1 - 0.85 * s), 1.1 * s, 1.7 * s, SQUARE);
+            } else {
+                let r = if p == Position::center() { 0.45 } else { 0.3 } * s;
+                self.circle(c, r, OUTLINE);
+                self.circle(c, r - (s * 0.06).max(1.), SQUARE);
+            }
+        }
+    }
+    fn board(&mut self, state: &State) {
+        let s = self.scale;
+        for i in 0..POSITIONS {
+            let p = Position::from(i);
+            let c = self.at(p);
+            let count = |first: bool| state.pieces(first).iter().filter(|q| **q == p).count();
+            let (first, second) = (count(true), count(false));
+            let both = first > 0 && second > 0;
+            for (n, color, dy) in [
+                (first, FIRST, if both { -0.4 } else { 0. }),
+                (second, SECOND, if both { 0.4 } else { 0. }),
+            ] {
+                if n == 0 {
+                    continue;
+                }
+                let c = (c.0, c.1 + dy * s);
+                self.circle(c, 0.27 * s, OUTLINE);
+                self.circle(c, 0.24 * s, color);
+                self.number(c, n, (s * 0.07).max(1.), WHITE);
+            }
+        }
+    }
+    fn arrow(&mut self, path: &[Position]) {
+        let s = self.scale;
+        let points: Vec<(f64, f64)> = path.iter().map(|p| self.at(*p)).collect();
+        for w in points.windows(2) {
+            self.line(w[0], w[1], s * 0.12, ARROW);
+        }
+        let (Some(&a), Some(&b)) = (points.iter().rev().nth(1), points.last()) else {
+            return;
+        };
+        let len = ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt().max(1e-9);
+        let (ux, uy) = ((b.0 - a.0) / len, (b.1 - a.1) / len);
+        let tip = (b.0 - ux * 0.2 * s, b.1 - uy * 0.2 * s);
+        let base = (tip.0 - ux * 0.35 * s, tip.1 - uy * 0.35 * s);
+        let side = (-uy * 0.2 * s, ux * 0.2 * s);
+        self.triangle(
+            [
+                tip,
+                (base.0 + side.0, base.1 + side.1),
+                (base.0 - side.0, base.1 - side.1),
+            ],
+            ARROW,
+        );
+    }
+    // The throw in the mover's color at the top left, with one pip per step.
+    fn throw(&mut self, n: usize, first: bool) {
+        let s = self.scale;
+        let color = if first { FIRST } else { SECOND };
+        self.number((s * 0.8, s * 0.6), n, s * 0.12, color);
+        for k in 0..n {
+            self.circle((s * 1.5 + k as f64 * s * 0.4, s * 0.6), s * 0.13, color);
+        }
+    }
+    fn flash(&mut self, p: Position) {
+        let s = self.scale;
+        self.ring(self.at(p), 0.5 * s, 0.12 * s, FLASH);
+    }
+    // The first side's share of the bar along the bottom edge is its win probability.
+    fn bar(&mut self, odds: Option<f64>) {
+        let Some(p) = odds else {
+            return;
+        };
+        let s = self.scale;
+        let (x, y) = (s, f64::from(self.height) - 0.8 * s);
+        let w = f64::from(self.width) - 2. * s;
+        self.rect((x - 1., y - 1.), w + 2., 0.5 * s + 2., OUTLINE);
+        self.rect((x, y), w, 0.5 * s, SECOND);
+        self.rect((x, y), w * p.clamp(0., 1.), 0.5 * s, FIRST);
+        self.rect((x + w / 2., y), 1., 0.5 * s, WHITE);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        agent::{Agent, Greedy},
+        tape::Tape,
+    };
+    fn game() -> Record {
+        let mut tape = Tape::seeded(7);
+        let mut state = State::new();
+        let mut record = Record::new(state.clone());
+        let mut greedy = Greedy::default();
+        while !state.is_end() {
+            let sticks = tape.throw().unwrap();
+            let actions = state.actions(sticks);
+            let act = greedy.choose(&state, sticks, &actions);
+            record.push(act);
+            state.act(act);
+        }
+        record
+    }
+    #[test]
+    fn frames() {
+        let record = game();
+        let states = record.states().unwrap();
+        let captures = record
+            .moves
+            .iter()
+            .zip(&states)
+            .filter(|(act, s)| s.describe(**act).captured > 0)
+            .count();
+        println!("{} moves, {} captures", record.moves.len(), captures);
+        let opt = GifOpt {
+            scale: 12,
+            delay: 50,
+            eval: None,
+        };
+        let bytes = render(&record, &opt).unwrap();
+        let mut decoder = gif::DecodeOptions::new().read_info(&bytes[..]).unwrap();
+        let (w, h) = (decoder.width(), decoder.height());
+        println!("{}x{}, {} bytes", w, h, bytes.len());
+        let mut frames = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!((frame.width, frame.height), (w, h));
+            frames += 1;
+        }
+        assert_eq!(frames, record.moves.len() + 4 * captures + 1);
+    }
+    #[test]
+    fn odds_bar() {
+        let mut record = Record::new(State::new());
+        let act = State::new().actions(crate::sticks::Sticks::from(3))[0];
+        record.push(act);
+        let with = render(&record, &GifOpt::default()).unwrap();
+        let without = render(
+            &record,
+            &GifOpt {
+                eval: None,
+                ..GifOpt::default()
+            },
+        )
+        .unwrap();
+        let height = |b: &[u8]| gif::DecodeOptions::new().read_info(b).unwrap().height();
+        assert!(height(&with) > height(&without));
+    }
+    #[test]
+    fn too_large() {
+        let opt = GifOpt {
+            scale: MAX_SCALE + 1,
+            ..GifOpt::default()
+        };
+        let e = render(&game(), &opt).unwrap_err();
+        println!("{}", e);
+        assert!(e.contains("scale"));
+        let r = Raster::new(MAX_SCALE, true).unwrap();
+        println!("{}x{}", r.width, r.height);
+    }
+}
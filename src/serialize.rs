@@ -0,0 +1,116 @@
+//! `serde` support, enabled by the `serde` feature.
+//!
+//! Positions are written by name (`K2_1`) and throws as their number of steps.
+//! Deserialization rejects data that no game could reach the shape of: wrong piece
+//! counts, unknown squares, impossible throws and a winner that disagrees with the pieces.
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    error::KariuchiError,
+    game::{Action, State, NUM_PIECES},
+    position::Position,
+    sticks::Sticks,
+};
+
+impl Serialize for Position {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Position {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for Sticks {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(usize::from(*self) as u8)
+    }
+}
+
+impl<'de> Deserialize<'de> for Sticks {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let n = u8::deserialize(deserializer)?;
+        Sticks::checked(n as usize).map_err(de::Error::custom)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Side {
+    First,
+    Second,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct StateRepr {
+    first: Vec<Position>,
+    second: Vec<Position>,
+    first_turn: bool,
+    winner: Option<Side>,
+}
+
+impl From<State> for StateRepr {
+    fn from(s: State) -> Self {
+        StateRepr {
+            first: s.pieces(true).to_vec(),
+            second: s.pieces(false).to_vec(),
+            first_turn: s.is_first(),
+            winner: match (s.is_end(), s.is_win_first()) {
+                (false, _) => None,
+                (true, true) => Some(Side::First),
+                (true, false) => Some(Side::Second),
+            },
+        }
+    }
+}
+
+impl TryFrom<StateRepr> for State {
+    type Error = KariuchiError;
+    fn try_from(r: StateRepr) -> Result<Self, Self::Error> {
+        let side = |v: Vec<Position>| -> Result<[Position; NUM_PIECES], KariuchiError> {
+            v.try_into()
+                .map_err(|v: Vec<_>| KariuchiError::PieceCount(v.len()))
+        };
+        let s = State::from_pieces(side(r.first)?, side(r.second)?, r.first_turn);
+        let consistent = match r.winner {
+            None => !s.is_end(),
+            Some(Side::First) => s.is_end() && s.is_win_first(),
+            Some(Side::Second) => s.is_end() && !s.is_win_first(),
+        };
+        if !consistent {
+            return Err(KariuchiError::WrongWinner);
+        }
+        Ok(s)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ActionRepr {
+    at: Position,
+    to: Position,
+    num: usize,
+    sticks: Sticks,
+}
+
+impl From<Action> for ActionRepr {
+    fn from(a: Action) -> Self {
+        ActionRepr {
+            at: a.at(),
+            to: a.to(),
+            num: a.num(),
+            sticks: a.sticks(),
+        }
+    }
+}
+
+impl TryFrom<ActionRepr> for Action {
+    type Error = KariuchiError;
+    fn try_from(r: ActionRepr) -> Result<Self, Self::Error> {
+        Action::try_new(r.at, r.to, r.num, r.sticks)
+    }
+}
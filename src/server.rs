@@ -0,0 +1,465 @@
+//! Local HTTP/JSON analysis server, enabled by the `server` feature.
+//!
+//! Every endpoint takes and returns JSON. Positions are the text written by
+//! `State`'s `Display` and moves are `Action` objects.
+//!
+//! - `POST /moves` `{position, sticks}`: legal moves with their descriptions.
+//! - `POST /apply` `{position, action}`: the position after a legal move.
+//! - `POST /evaluate` `{position, ply?, rollout?, time_limit_ms?}`: win probability of the
+//!   side to move.
+//! - `POST /best` `{position, sticks, ply?, rollout?, time_limit_ms?}`: the best move.
+//! - `POST /jobs` `{kind: "evaluate" | "best", ...}`: starts a background analysis.
+//! - `GET /jobs/{id}` polls it and `DELETE /jobs/{id}` cancels it.
+//!
+//! Without a time limit a search runs once at the requested ply. With one it deepens one
+//! ply at a time and answers with the deepest ply that finished. `ply` is capped at
+//! `MAX_PLY` and `rollout` at `MAX_ROLLOUT`; replies give the ply actually searched.
+//!
+//! Every search stops at a deadline: `time_limit_ms`, at most `MAX_TIME_LIMIT`, or without
+//! one `TIME_LIMIT` for `/evaluate` and `/best` and `MAX_TIME_LIMIT` for jobs. At most
+//! `MAX_SEARCHES` searches and jobs run at once; more are refused with 429.
+//!
+//! Finished jobs are kept for `JOB_TTL` and at most `MAX_FINISHED_JOBS` of them, so poll
+//! soon after a job ends.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    evaluate::{Evaluate, Opt},
+    game::{Action, State},
+    sticks::Sticks,
+};
+
+/// Deepest search a request may ask for.
+pub const MAX_PLY: usize = 4;
+/// Most playouts per leaf a request may ask for.
+pub const MAX_ROLLOUT: usize = 1000;
+/// Deadline of `/evaluate` and `/best` without a `time_limit_ms`.
+pub const TIME_LIMIT: Duration = Duration::from_secs(10);
+/// Longest any search may run, and the deadline of jobs without a `time_limit_ms`.
+pub const MAX_TIME_LIMIT: Duration = Duration::from_secs(300);
+/// Searches, synchronous or jobs, running at once.
+pub const MAX_SEARCHES: usize = 4;
+/// How long a finished job stays available to `GET /jobs/{id}`.
+pub const JOB_TTL: Duration = Duration::from_secs(600);
+/// Finished jobs kept at most; the oldest go first.
+pub const MAX_FINISHED_JOBS: usize = 256;
+
+type Reply = Result<Value, (u16, String)>;
+
+fn bad_request(e: impl ToString) -> (u16, String) {
+    (400, e.to_string())
+}
+
+#[derive(Deserialize)]
+struct MovesRequest {
+    position: String,
+    sticks: Sticks,
+}
+
+#[derive(Deserialize)]
+struct ApplyRequest {
+    position: String,
+    action: Action,
+}
+
+#[derive(Deserialize, Clone)]
+struct SearchRequest {
+    position: String,
+    sticks: Option<Sticks>,
+    #[serde(default = "default_ply")]
+    ply: usize,
+    #[serde(default = "default_rollout")]
+    rollout: usize,
+    time_limit_ms: Option<u64>,
+}
+
+fn default_ply() -> usize {
+    2
+}
+
+fn default_rollout() -> usize {
+    10
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum JobKind {
+    Evaluate,
+    Best,
+}
+
+#[derive(Deserialize)]
+struct JobRequest {
+    kind: JobKind,
+    #[serde(flatten)]
+    search: SearchRequest,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(tag = "status", rename_all = "lowercase")]
+enum Job {
+    Running,
+    Done { result: Value },
+    Failed { error: String },
+    Cancelled,
+}
+
+// A running search, counted in `Server::searches` until dropped.
+struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+struct Entry {
+    job: Job,
+    stop: Arc<AtomicBool>,
+    finished: Option<Instant>,
+}
+
+#[derive(Default)]
+struct Jobs {
+    next: u64,
+    jobs: HashMap<u64, Entry>,
+}
+
+impl Jobs {
+    // Drops finished jobs past `JOB_TTL`, then the oldest beyond `MAX_FINISHED_JOBS`.
+    fn prune(&mut self) {
+        let now = Instant::now();
+        self.jobs
+            .retain(|_, e| !matches!(e.finished, Some(t) if now.duration_since(t) >= JOB_TTL));
+        let mut done: Vec<(Instant, u64)> = self
+            .jobs
+            .iter()
+            .filter_map(|(id, e)| e.finished.map(|t| (t, *id)))
+            .collect();
+        if done.len() > MAX_FINISHED_JOBS {
+            done.sort_unstable();
+            for (_, id) in &done[..done.len() - MAX_FINISHED_JOBS] {
+                self.jobs.remove(id);
+            }
+        }
+    }
+    fn finish(&mut self, id: u64, job: Job) {
+        if let Some(e) = self.jobs.get_mut(&id) {
+            e.job = job;
+            e.finished.get_or_insert_with(Instant::now);
+        }
+        self.prune();
+    }
+}
+
+/// The analysis server. `run` serves requests until `stop` is called.
+#[derive(Clone)]
+pub struct Server {
+    http: Arc<tiny_http::Server>,
+    jobs: Arc<Mutex<Jobs>>,
+    searches: Arc<AtomicUsize>,
+}
+
+impl Server {
+    /// Listens on `addr`, e.g. `127.0.0.1:8080`; port 0 picks a free port.
+    pub fn bind(addr: &str) -> Result<Self, String> {
+        let http = tiny_http::Server::http(addr).map_err(|e| format!("{}: {}", addr, e))?;
+        Ok(Server {
+            http: Arc::new(http),
+            jobs: Arc::new(Mutex::new(Jobs::default())),
+            searches: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+    /// The address the server listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.http.server_addr().to_ip().unwrap()
+    }
+    /// Answers requests, each on its own thread, until the process ends.
+    pub fn run(&self) {
+        for mut req in self.http.incoming_requests() {
+            let server = self.clone();
+            std::thread::spawn(move || {
+                let mut body = String::new();
+                let reply = match req.as_reader().read_to_string(&mut body) {
+                    Ok(_) => server.route(req.method(), req.url(), &body),
+                    Err(e) => Err(bad_request(e)),
+                };
+                let (code, value) = match reply {
+                    Ok(v) => (200, v),
+                    Err((code, e)) => (code, json!({ "error": e })),
+                };
+                let header =
+                    tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+                        .unwrap();
+                let res = tiny_http::Response::from_string(value.to_string())
+                    .with_status_code(code)
+                    .with_header(header);
+                let _ = req.respond(res);
+            });
+        }
+    }
+    /// Makes `run` return.
+    pub fn stop(&self) {
+        self.http.unblock();
+    }
+    fn route(&self, method: &tiny_http::Method, url: &str, body: &str) -> Reply {
+        use tiny_http::Method::{Delete, Get, Post};
+        let path = url.split('?').next().unwrap_or(url);
+        match (method, path) {
+            (Post, "/moves") => moves(parse(body)?),
+            (Post, "/apply") => apply(parse(body)?),
+            (Post, "/evaluate") => {
+                let _slot = self.slot()?;
+                evaluate(parse(body)?, None, TIME_LIMIT)
+            }
+            (Post, "/best") => {
+                let _slot = self.slot()?;
+                best(parse(body)?, None, TIME_LIMIT)
+            }
+            (Post, "/jobs") => self.start_job(parse(body)?),
+            (Get, _) | (Delete, _) if path.starts_with("/jobs/") => {
+                let id = path["/jobs/".len()..]
+                    .parse::<u64>()
+                    .map_err(|_| (404, format!("no job {}", path)))?;
+                self.job(id, *method == Delete)
+            }
+            _ => Err((404, format!("no endpoint {} {}", method, path))),
+        }
+    }
+    // Room for one more search, or 429 when `MAX_SEARCHES` are running.
+    fn slot(&self) -> Result<Slot, (u16, String)> {
+        let slot = Slot(self.searches.clone());
+        if self.searches.fetch_add(1, Ordering::SeqCst) >= MAX_SEARCHES {
+            return Err((429, "too many searches running".to_string()));
+        }
+        Ok(slot)
+    }
+    fn start_job(&self, req: JobRequest) -> Reply {
+        // Fail fast on a bad position instead of reporting it through the job.
+        state_of(&req.search.position)?;
+        let slot = self.slot()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let id = {
+            let mut jobs = self.jobs.lock().unwrap();
+            jobs.prune();
+            let id = jobs.next;
+            jobs.next += 1;
+            let entry = Entry {
+                job: Job::Running,
+                stop: stop.clone(),
+                finished: None,
+            };
+            jobs.jobs.insert(id, entry);
+            id
+        };
+        let jobs = self.jobs.clone();
+        std::thread::spawn(move || {
+            let res = match req.kind {
+                JobKind::Evaluate => evaluate(req.search, Some(stop.clone()), MAX_TIME_LIMIT),
+                JobKind::Best => best(req.search, Some(stop.clone()), MAX_TIME_LIMIT),
+            };
+            drop(slot);
+            let job = match res {
+                _ if stop.load(Ordering::Relaxed) => Job::Cancelled,
+                Ok(result) => Job::Done { result },
+                Err((_, error)) => Job::Failed { error },
+            };
+            let mut jobs = jobs.lock().unwrap();
+            // A job cancelled while running stays cancelled.
+            if let Some(Entry {
+                job: Job::Running, ..
+            }) = jobs.jobs.get(&id)
+            {
+                jobs.finish(id, job);
+            }
+        });
+        Ok(json!({ "id": id }))
+    }
+    fn job(&self, id: u64, cancel: bool) -> Reply {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.prune();
+        let entry = jobs
+            .jobs
+            .get(&id)
+            .ok_or_else(|| (404, format!("no job {}", id)))?;
+        if cancel {
+            entry.stop.store(true, Ordering::Relaxed);
+            if let Job::Running = entry.job {
+                jobs.finish(id, Job::Cancelled);
+            }
+        }
+        let job = jobs.jobs.get(&id).map_or(Job::Cancelled, |e| e.job.clone());
+        let mut v = serde_json::to_value(&job).unwrap();
+        v["id"] = json!(id);
+        Ok(v)
+    }
+}
+
+fn parse<'a, T: Deserialize<'a>>(body: &'a str) -> Result<T, (u16, String)> {
+    serde_json::from_str(body).map_err(bad_request)
+}
+
+fn state_of(position: &str) -> Result<State, (u16, String)> {
+    position.parse().map_err(bad_request)
+}
+
+fn moves(req: MovesRequest) -> Reply {
+    let state = state_of(&req.position)?;
+    if state.is_end() {
+        return Ok(json!({ "moves": [] }));
+    }
+    let moves: Vec<Value> = state
+        .actions(req.sticks)
+        .into_iter()
+        .map(|act| {
+            let mut v = serde_json::to_value(state.describe(act)).unwrap();
+            v["text"] = json!(state.describe(act).to_string());
+            v
+        })
+        .collect();
+    Ok(json!({ "moves": moves }))
+}
+
+fn apply(req: ApplyRequest) -> Reply {
+    let mut state = state_of(&req.position)?;
+    state.try_act(req.action).map_err(bad_request)?;
+    Ok(json!({ "position": state.to_string(), "state": state }))
+}
+
+// When the search must stop: the requested time limit within `MAX_TIME_LIMIT`, or `default`.
+fn deadline(req: &SearchRequest, default: Duration) -> Instant {
+    let limit = req.time_limit_ms.map_or(default, Duration::from_millis);
+    Instant::now() + limit.min(MAX_TIME_LIMIT)
+}
+
+fn limits(
+    req: &SearchRequest,
+    stop: Option<Arc<AtomicBool>>,
+    default: Duration,
+    e: Evaluate,
+) -> Evaluate {
+    let e = e.with_deadline(deadline(req, default));
+    match stop {
+        Some(stop) => e.with_stop(stop),
+        None => e,
+    }
+}
+
+// The requested search, within `MAX_PLY` and `MAX_ROLLOUT`.
+fn opt_of(req: &SearchRequest) -> Opt {
+    Opt {
+        ply: req.ply.min(MAX_PLY),
+        rollout: req.rollout.clamp(1, MAX_ROLLOUT),
+    }
+}
+
+fn evaluate(req: SearchRequest, stop: Option<Arc<AtomicBool>>, default: Duration) -> Reply {
+    let state = state_of(&req.position)?;
+    let opt = opt_of(&req);
+    let e = limits(&req, stop, default, Evaluate::new(opt, state));
+    let found = match req.time_limit_ms {
+        Some(_) => e.evaluate_deepening(),
+        None => e.try_evaluate().map(|v| (opt.ply, v)),
+    };
+    let (ply, value) = found.ok_or_else(|| (503, "no result within the time limit".to_string()))?;
+    Ok(json!({ "value": value, "ply": ply }))
+}
+
+fn best(req: SearchRequest, stop: Option<Arc<AtomicBool>>, default: Duration) -> Reply {
+    let state = state_of(&req.position)?;
+    let sticks = req.sticks.ok_or_else(|| bad_request("missing sticks"))?;
+    if state.is_end() {
+        return Err(bad_request("the game is over"));
+    }
+    let actions = state.actions(sticks);
+    // One shared deadline for every root move; with a time limit, deepen until it passes.
+    let deadline = deadline(&req, default);
+    let max = opt_of(&req);
+    let first = if req.time_limit_ms.is_some() {
+        0
+    } else {
+        max.ply
+    };
+    let mut found = None;
+    for ply in first..=max.ply {
+        let opt = Opt { ply, ..max };
+        let values: Option<Vec<f64>> = actions
+            .par_iter()
+            .map(|act| {
+                let mut after = state.clone();
+                after.act(*act);
+                let mut e = Evaluate::new(opt, after.clone()).with_deadline(deadline);
+                if let Some(stop) = &stop {
+                    e = e.with_stop(stop.clone());
+                }
+                let p = e.try_evaluate()?;
+                Some(if after.is_first() == state.is_first() {
+                    p
+                } else {
+                    1. - p
+                })
+            })
+            .collect();
+        match values {
+            Some(v) => found = Some((ply, v)),
+            None => break,
+        }
+    }
+    let (ply, values) =
+        found.ok_or_else(|| (503, "no result within the time limit".to_string()))?;
+    let (i, value) = values
+        .iter()
+        .copied()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap();
+    let alternatives: Vec<Value> = actions
+        .iter()
+        .zip(&values)
+        .map(|(act, v)| json!({ "action": act, "text": act.to_string(), "value": v }))
+        .collect();
+    Ok(json!({
+        "action": actions[i],
+        "text": state.describe(actions[i]).to_string(),
+        "value": value,
+        "ply": ply,
+        "alternatives": alternatives,
+    }))
+}
+
+/// The `serve` subcommand: serves on localhost, at `--port` or 8080, until the process ends.
+pub fn main(args: &[String]) -> Result<(), String> {
+    let mut addr = "127.0.0.1:8080".to_string();
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--port" => {
+                let port = it.next().ok_or("--port needs a value")?;
+                addr = format!("127.0.0.1:{}", port);
+            }
+            _ => {
+                return Err(format!(
+                    "unknown option {}\nusage: kariuchi-solver serve [--port P]",
+                    arg
+                ))
+            }
+        }
+    }
+    let server = Server::bind(&addr)?;
+    println!("listening on http://{}", server.addr());
+    server.run();
+    Ok(())
+}
@@ -0,0 +1,152 @@
+//! What a search cost: nodes per depth, chance nodes and playouts, gathered while
+//! `Evaluate` runs and logged after each move through the `log` facade. The search keeps
+//! no transposition table, so there are no cache hits to count.
+
+use std::{
+    fmt::{Display, Formatter},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::game::Action;
+
+/// Counters a search fills in through `Evaluate::with_stats`; searches on several threads
+/// may share one.
+#[derive(Debug)]
+pub struct Stats {
+    nodes: Vec<AtomicU64>,
+    chance: AtomicU64,
+    rollouts: AtomicU64,
+    rollout_moves: AtomicU64,
+}
+
+impl Stats {
+    /// Counters for a search `ply` deep: positions are counted by their depth from the
+    /// searched one, 0 to `ply`.
+    pub fn new(ply: usize) -> Self {
+        Stats {
+            nodes: (0..=ply).map(|_| AtomicU64::new(0)).collect(),
+            chance: AtomicU64::new(0),
+            rollouts: AtomicU64::new(0),
+            rollout_moves: AtomicU64::new(0),
+        }
+    }
+    pub(crate) fn node(&self, depth: usize) {
+        if let Some(n) = self.nodes.get(depth) {
+            n.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    pub(crate) fn chance(&self) {
+        self.chance.fetch_add(1, Ordering::Relaxed);
+    }
+    pub(crate) fn rollout(&self, moves: usize) {
+        self.rollouts.fetch_add(1, Ordering::Relaxed);
+        self.rollout_moves
+            .fetch_add(moves as u64, Ordering::Relaxed);
+    }
+    /// Positions searched at each depth.
+    pub fn nodes(&self) -> Vec<u64> {
+        self.nodes
+            .iter()
+            .map(|n| n.load(Ordering::Relaxed))
+            .collect()
+    }
+    /// Positions where the throws were averaged.
+    pub fn chance_nodes(&self) -> u64 {
+        self.chance.load(Ordering::Relaxed)
+    }
+    /// Playouts run at the leaves.
+    pub fn rollouts(&self) -> u64 {
+        self.rollouts.load(Ordering::Relaxed)
+    }
+    /// Moves played in all rollouts together.
+    pub fn rollout_moves(&self) -> u64 {
+        self.rollout_moves.load(Ordering::Relaxed)
+    }
+}
+
+/// The cost of choosing one move: the totals of `Stats` over every candidate, the time
+/// spent on each candidate and the wall-clock time of the whole search.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    /// `Stats::nodes`.
+    pub nodes: Vec<u64>,
+    /// `Stats::chance_nodes`.
+    pub chance_nodes: u64,
+    /// `Stats::rollouts`.
+    pub rollouts: u64,
+    /// `Stats::rollout_moves`.
+    pub rollout_moves: u64,
+    /// Search time per candidate, added up over its tries and threads.
+    pub actions: Vec<(Action, Duration)>,
+    /// Wall-clock time of the whole search.
+    pub elapsed: Duration,
+}
+
+impl Report {
+    /// The totals of `stats` with the time per candidate and in all.
+    pub fn new(stats: &Stats, actions: Vec<(Action, Duration)>, elapsed: Duration) -> Self {
+        Report {
+            nodes: stats.nodes(),
+            chance_nodes: stats.chance_nodes(),
+            rollouts: stats.rollouts(),
+            rollout_moves: stats.rollout_moves(),
+            actions,
+            elapsed,
+        }
+    }
+    /// Positions searched at every depth together.
+    pub fn total_nodes(&self) -> u64 {
+        self.nodes.iter().sum()
+    }
+    /// Moves per playout; 0 without playouts.
+    pub fn average_rollout_length(&self) -> f64 {
+        if self.rollouts == 0 {
+            0.
+        } else {
+            self.rollout_moves as f64 / self.rollouts as f64
+        }
+    }
+    /// `total_nodes` over the wall-clock time.
+    pub fn nodes_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0. {
+            0.
+        } else {
+            self.total_nodes() as f64 / secs
+        }
+    }
+    /// Logs the summary at `info`, then nodes per depth and time per candidate at `debug`.
+    pub fn log(&self, who: &str) {
+        log::info!("{}: {}", who, self);
+        if !log::log_enabled!(log::Level::Debug) {
+            return;
+        }
+        for (depth, n) in self.nodes.iter().enumerate() {
+            log::debug!("{}: depth {}: {} nodes", who, depth, n);
+        }
+        for (act, time) in &self.actions {
+            log::debug!(
+                "{}: {: <15} {:.1} ms",
+                who,
+                act.to_string(),
+                time.as_secs_f64() * 1000.
+            );
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} nodes ({} chance) in {:.1} ms, {:.0} nodes/s, {} rollouts of {:.1} moves",
+            self.total_nodes(),
+            self.chance_nodes,
+            self.elapsed.as_secs_f64() * 1000.,
+            self.nodes_per_second(),
+            self.rollouts,
+            self.average_rollout_length()
+        )
+    }
+}
@@ -1,12 +1,31 @@
+//! `Sticks`, the outcome of throwing the four sticks, and the odds of each.
+
+use crate::error::KariuchiError;
+
 const NUM_STICKS: usize = 4;
+/// The outcome of throwing the four sticks: the number of steps to move, 1 to 5.
+/// Throwing 4 or 5 lets the same player move again.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Sticks(usize);
 
 impl Sticks {
+    /// The throw moving `x` steps, or an error unless `x` is 1 to 5.
+    pub fn checked(x: usize) -> Result<Self, KariuchiError> {
+        if (1..=NUM_STICKS + 1).contains(&x) {
+            Ok(Sticks(x))
+        } else {
+            Err(KariuchiError::NoSuchThrow(x))
+        }
+    }
+    /// A random throw, with the odds of `all_sticks`.
     pub fn throw() -> Self {
+        Self::throw_with(&mut rand::thread_rng())
+    }
+    /// `throw` drawing from `rng`.
+    pub fn throw_with<R: rand::Rng>(rng: &mut R) -> Self {
         let mut num = 0;
         for _ in 0..NUM_STICKS {
-            num += rand::random::<usize>() % 2;
+            num += rng.gen_range(0..2);
         }
         if num == 0 {
             Sticks(NUM_STICKS + 1)
@@ -14,12 +33,13 @@ impl Sticks {
             Sticks(num)
         }
     }
+    /// Every outcome with its probability.
     pub fn all_sticks() -> Vec<(f64, Self)> {
         let mut vec = Vec::new();
         let mut over = 1.;
         let mut under = 1.;
         for i in 0..=NUM_STICKS {
-            let p = over / under / (2f64.powf(NUM_STICKS as f64) as f64);
+            let p = over / under / 2f64.powf(NUM_STICKS as f64);
             let c = if i == 0 { 5 } else { i };
             under *= (i + 1) as f64;
             over *= (NUM_STICKS - i) as f64;
@@ -27,6 +47,7 @@ impl Sticks {
         }
         vec
     }
+    /// Whether the thrower moves again after this throw.
     pub fn replay(&self) -> bool {
         self.0 >= NUM_STICKS
     }
@@ -52,4 +73,13 @@ mod test {
             println!("{} {}", usize::from(s), p);
         }
     }
+    #[test]
+    fn seeded_throws() {
+        use rand::SeedableRng;
+        let mut a = rand::rngs::StdRng::seed_from_u64(7);
+        let mut b = rand::rngs::StdRng::seed_from_u64(7);
+        for _ in 0..100 {
+            assert_eq!(Sticks::throw_with(&mut a), Sticks::throw_with(&mut b));
+        }
+    }
 }
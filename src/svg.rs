@@ -0,0 +1,314 @@
+//! SVG drawings of a `State`, laid out by `position::coordinates`.
+
+use std::fmt::Write;
+
+use crate::{
+    game::{self, Action, State},
+    position::{self, HumanPosition, Position, POSITIONS},
+    sticks::Sticks,
+};
+
+const FIRST_COLOR: &str = "#d33";
+const SECOND_COLOR: &str = "#36c";
+
+/// Drawing settings.
+#[derive(Debug, Clone)]
+pub struct SvgOpt {
+    /// Pixels per square.
+    pub scale: f64,
+    /// Whether to write the name of every square.
+    pub labels: bool,
+}
+
+impl Default for SvgOpt {
+    fn default() -> Self {
+        SvgOpt {
+            scale: 40.,
+            labels: true,
+        }
+    }
+}
+
+/// Draws `state` with an arrow along the path of each of `arrows`.
+pub fn render(state: &State, arrows: &[Action], opt: &SvgOpt) -> String {
+    Canvas::new(opt).draw(state, arrows)
+}
+
+/// Draws `state` with arrows for every move of the side to move with `sticks`.
+pub fn render_throw(state: &State, sticks: Sticks, opt: &SvgOpt) -> String {
+    render(state, &state.actions(sticks), opt)
+}
+
+/// The squares an action passes through, from `at` to `to`.
+pub fn path(act: &Action) -> Vec<Position> {
+    let d = usize::from(act.sticks());
+    let mut v = vec![act.at()];
+    let Some(branch) = act.at().advance(d).iter().position(|p| *p == act.to()) else {
+        v.push(act.to());
+        return v;
+    };
+    for k in 1..=d {
+        let step = act.at().advance(k);
+        let p = *step.get(branch).unwrap_or(&step[0]);
+        if p != *v.last().unwrap() {
+            v.push(p);
+        }
+        if p == Position::goal() {
+            break;
+        }
+    }
+    v
+}
+
+struct Canvas<'a> {
+    opt: &'a SvgOpt,
+    coords: Vec<(f64, f64)>,
+    out: String,
+}
+
+impl<'a> Canvas<'a> {
+    fn new(opt: &'a SvgOpt) -> Self {
+        Canvas {
+            opt,
+            coords: position::coordinates(),
+            out: String::new(),
+        }
+    }
+    // Pixel position of `p`, leaving a margin around the board.
+    fn at(&self, p: Position) -> (f64, f64) {
+        let (x, y) = self.coords[usize::from(p)];
+        let s = self.opt.scale;
+        (x * s + 2.5 * s, y * s + s)
+    }
+    fn size(&self) -> (f64, f64) {
+        let s = self.opt.scale;
+        let w = self.coords.iter().map(|c| c.0).fold(0., f64::max);
+        let h = self.coords.iter().map(|c| c.1).fold(0., f64::max);
+        (w * s + 5. * s, h * s + 2. * s)
+    }
+    fn draw(mut self, state: &State, arrows: &[Action]) -> String {
+        let (w, h) = self.size();
+        let _ = writeln!(
+            self.out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w:.0}" height="{h:.0}" viewBox="0 0 {w:.0} {h:.0}" font-family="sans-serif" text-anchor="middle" dominant-baseline="central">"#
+        );
+        let _ = writeln!(
+            self.out,
+            r##"<defs><marker id="arrow" viewBox="0 0 10 10" refX="8" refY="5" markerWidth="6" markerHeight="6" orient="auto-start-reverse"><path d="M 0 0 L 10 5 L 0 10 z" fill="#2a2"/></marker></defs>"##
+        );
+        let _ = writeln!(
+            self.out,
+            r##"<rect width="{w:.0}" height="{h:.0}" fill="#f4ecd8"/>"##
+        );
+        self.edges();
+        self.squares();
+        self.pieces(state);
+        self.arrows(arrows);
+        let _ = writeln!(self.out, "</svg>");
+        self.out
+    }
+    fn edges(&mut self) {
+        let s = self.opt.scale;
+        for (a, b) in position::edges() {
+            let (ax, ay) = self.at(a);
+            let (bx, by) = self.at(b);
+            let on_route = |p: Position| matches!(p.into(), HumanPosition::ROUTE(_));
+            if on_route(a) && on_route(b) && ay != by {
+                // The route turns around the right end of the board.
+                let r = s * 1.5;
+                let _ = writeln!(
+                    self.out,
+                    r##"<path d="M {ax:.1} {ay:.1} C {:.1} {ay:.1} {:.1} {by:.1} {bx:.1} {by:.1}" fill="none" stroke="#886" stroke-width="3"/>"##,
+                    ax + r,
+                    bx + r
+                );
+            } else {
+                let _ = writeln!(
+                    self.out,
+                    r##"<line x1="{ax:.1}" y1="{ay:.1}" x2="{bx:.1}" y2="{by:.1}" stroke="#886" stroke-width="3"/>"##
+                );
+            }
+        }
+    }
+    fn squares(&mut self) {
+        let s = self.opt.scale;
+        for i in 0..POSITIONS {
+            let p = Position::from(i);
+            let (x, y) = self.at(p);
+            match p.into() {
+                HumanPosition::START | HumanPosition::GOAL => {
+                    let _ = writeln!(
+                        self.out,
+                        r##"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" rx="{:.1}" fill="#e8dcc0" stroke="#553" stroke-width="2"/>"##,
+                        x - s * 0.6,
+                        y - s * 0.9,
+                        s * 1.2,
+                        s * 1.8,
+                        s * 0.2
+                    );
+                }
+                HumanPosition::CENTER => {
+                    let _ = writeln!(
+                        self.out,
+                        r##"<circle cx="{x:.1}" cy="{y:.1}" r="{:.1}" fill="#fff8e8" stroke="#553" stroke-width="3"/>"##,
+                        s * 0.45
+                    );
+                }
+                HumanPosition::ROUTE(r) if r % 5 == 0 => {
+                    // Corners where the shortcuts meet the route.
+                    let _ = writeln!(
+                        self.out,
+                        r##"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="#fff8e8" stroke="#553" stroke-width="2"/>"##,
+                        x - s * 0.35,
+                        y - s * 0.35,
+                        s * 0.7,
+                        s * 0.7
+                    );
+                }
+                _ => {
+                    let _ = writeln!(
+                        self.out,
+                        r##"<circle cx="{x:.1}" cy="{y:.1}" r="{:.1}" fill="#fff8e8" stroke="#553" stroke-width="1.5"/>"##,
+                        s * 0.3
+                    );
+                }
+            }
+            if self.opt.labels {
+                let _ = writeln!(
+                    self.out,
+                    r##"<text x="{x:.1}" y="{:.1}" font-size="{:.1}" fill="#775">{}</text>"##,
+                    y + s * 0.55,
+                    s * 0.22,
+                    p
+                );
+            }
+        }
+    }
+    fn pieces(&mut self, state: &State) {
+        let s = self.opt.scale;
+        for i in 0..POSITIONS {
+            let p = Position::from(i);
+            let (x, y) = self.at(p);
+            let count = |first: bool| state.pieces(first).iter().filter(|q| **q == p).count();
+            let (first, second) = (count(true), count(false));
+            // Only the start and goal can hold both sides; stack them one above the other.
+            let both = first > 0 && second > 0;
+            for (n, color, dy) in [
+                (first, FIRST_COLOR, if both { -0.4 } else { 0. }),
+                (second, SECOND_COLOR, if both { 0.4 } else { 0. }),
+            ] {
+                if n == 0 {
+                    continue;
+                }
+                let cy = y + dy * s;
+                let _ = writeln!(
+                    self.out,
+                    r##"<circle cx="{x:.1}" cy="{cy:.1}" r="{:.1}" fill="{color}" stroke="#222" stroke-width="1.5"/>"##,
+                    s * 0.26
+                );
+                let _ = writeln!(
+                    self.out,
+                    r##"<text x="{x:.1}" y="{cy:.1}" font-size="{:.1}" fill="#fff" font-weight="bold">{n}</text>"##,
+                    s * 0.3
+                );
+            }
+        }
+    }
+    fn arrows(&mut self, arrows: &[Action]) {
+        let mut drawn: Vec<(Position, Position)> = vec![];
+        for act in arrows {
+            if drawn.contains(&(act.at(), act.to())) {
+                continue;
+            }
+            drawn.push((act.at(), act.to()));
+            let points: Vec<String> = path(act)
+                .iter()
+                .map(|p| {
+                    let (x, y) = self.at(*p);
+                    format!("{:.1},{:.1}", x, y)
+                })
+                .collect();
+            let _ = writeln!(
+                self.out,
+                r##"<polyline points="{}" fill="none" stroke="#2a2" stroke-width="{:.1}" stroke-opacity="0.8" marker-end="url(#arrow)"/>"##,
+                points.join(" "),
+                self.opt.scale * 0.08
+            );
+        }
+    }
+}
+
+/// The `svg` subcommand: draws the position in POSITION_FILE (or stdin), written as
+/// `State` displays it.
+pub fn main(args: &[String]) -> Result<(), String> {
+    let mut throw = None;
+    let mut out = None;
+    let mut input = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--sticks" => {
+                let n: usize = value()?.parse().map_err(|e| format!("--sticks: {}", e))?;
+                throw = Some(Sticks::checked(n).map_err(|e| format!("--sticks: {}", e))?);
+            }
+            "--out" => out = Some(value()?.clone()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => input = Some(arg.clone()),
+        }
+    }
+    let state = game::read_position(input)?;
+    let opt = SvgOpt::default();
+    let image = match throw {
+        Some(s) => render_throw(&state, s, &opt),
+        None => render(&state, &[], &opt),
+    };
+    match out {
+        Some(path) => std::fs::write(&path, image).map_err(|e| format!("{}: {}", path, e)),
+        None => {
+            print!("{}", image);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::NUM_PIECES;
+    #[test]
+    fn start_position() {
+        let s = State::new();
+        let svg = render_throw(&s, Sticks::from(3), &SvgOpt::default());
+        println!("{}", svg);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<polyline").count(), 1);
+        assert!(svg.contains(&format!(">{}</text>", NUM_PIECES)));
+        assert!(svg.contains(">K2_1</text>"));
+    }
+    #[test]
+    fn paths() {
+        let corner = Position::route(5);
+        let s = State::from_pieces([corner; NUM_PIECES], [Position::start(); NUM_PIECES], true);
+        for act in s.actions(Sticks::from(5)) {
+            let p = path(&act);
+            let text: Vec<String> = p.iter().map(|x| x.to_string()).collect();
+            println!("{}: {}", act, text.join(" "));
+            assert_eq!(p[0], act.at());
+            assert_eq!(*p.last().unwrap(), act.to());
+        }
+        let svg = render_throw(&s, Sticks::from(5), &SvgOpt::default());
+        assert_eq!(svg.matches("<polyline").count(), 2);
+    }
+    #[test]
+    fn no_labels() {
+        let opt = SvgOpt {
+            scale: 20.,
+            labels: false,
+        };
+        let svg = render(&State::new(), &[], &opt);
+        assert!(!svg.contains(">R1<"));
+        assert!(!svg.contains("<polyline"));
+    }
+}
@@ -0,0 +1,125 @@
+//! `Tape`, a sequence of throws that can be seeded, saved and replayed.
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{error::KariuchiError, sticks::Sticks};
+
+/// A sequence of throws consumed by the game loop in place of `Sticks::throw`.
+/// Every throw handed out is recorded, so the tape can be rewound, saved and replayed.
+#[derive(Debug, Clone)]
+pub struct Tape {
+    throws: Vec<Sticks>,
+    pos: usize,
+    // Source of new throws once the recorded ones run out; a loaded tape has none.
+    rng: Option<StdRng>,
+}
+
+impl Tape {
+    /// An empty tape that draws new throws from `seed`, the same ones on every run.
+    pub fn seeded(seed: u64) -> Self {
+        Tape {
+            throws: vec![],
+            pos: 0,
+            rng: Some(StdRng::seed_from_u64(seed)),
+        }
+    }
+    /// An empty tape that draws new throws from system randomness.
+    pub fn random() -> Self {
+        Tape {
+            throws: vec![],
+            pos: 0,
+            rng: Some(StdRng::from_entropy()),
+        }
+    }
+    /// A tape of `throws` that ends after the last one.
+    pub fn from_throws(throws: Vec<Sticks>) -> Self {
+        Tape {
+            throws,
+            pos: 0,
+            rng: None,
+        }
+    }
+    /// The next throw, or `None` when a fixed tape is exhausted.
+    pub fn throw(&mut self) -> Option<Sticks> {
+        if self.pos == self.throws.len() {
+            let rng = self.rng.as_mut()?;
+            self.throws.push(Sticks::throw_with(rng));
+        }
+        self.pos += 1;
+        Some(self.throws[self.pos - 1])
+    }
+    /// Starts over from the first throw; a seeded tape then yields the same sequence again.
+    pub fn rewind(&mut self) {
+        self.pos = 0;
+    }
+    /// Throws handed out so far.
+    pub fn played(&self) -> &[Sticks] {
+        &self.throws[..self.pos]
+    }
+    /// Every throw on the tape, played or not, as numbers separated by spaces. `load` reads
+    /// them back into a tape positioned at the first throw.
+    pub fn save(&self) -> String {
+        let v: Vec<String> = self
+            .throws
+            .iter()
+            .map(|s| usize::from(*s).to_string())
+            .collect();
+        v.join(" ") + "\n"
+    }
+    /// Reads a tape written by `save`, failing on a word that is not a throw.
+    pub fn load(text: &str) -> Result<Self, KariuchiError> {
+        let mut throws = vec![];
+        for word in text.split_whitespace() {
+            let s = word
+                .parse::<usize>()
+                .map_err(|_| KariuchiError::BadThrow(word.to_string()))
+                .and_then(Sticks::checked)?;
+            throws.push(s);
+        }
+        Ok(Tape::from_throws(throws))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn rewind() {
+        let mut t = Tape::seeded(3);
+        let a: Vec<Sticks> = (0..10).map(|_| t.throw().unwrap()).collect();
+        t.rewind();
+        let b: Vec<Sticks> = (0..20).map(|_| t.throw().unwrap()).collect();
+        assert_eq!(a[..], b[..10]);
+        let mut fresh = Tape::seeded(3);
+        let c: Vec<Sticks> = (0..20).map(|_| fresh.throw().unwrap()).collect();
+        assert_eq!(b, c);
+    }
+    #[test]
+    fn save_load() {
+        let mut t = Tape::seeded(5);
+        for _ in 0..30 {
+            t.throw();
+        }
+        let text = t.save();
+        println!("{}", text);
+        let mut loaded = Tape::load(&text).unwrap();
+        assert_eq!(loaded.save(), text);
+        loaded.throw();
+        assert_eq!(loaded.save(), text);
+        assert_eq!(Tape::load(&loaded.save()).unwrap().save(), text);
+        loaded.rewind();
+        t.rewind();
+        for _ in 0..30 {
+            assert_eq!(loaded.throw(), t.throw());
+        }
+        assert_eq!(loaded.throw(), None);
+        assert_eq!(
+            Tape::load("1 2 6").err(),
+            Some(KariuchiError::NoSuchThrow(6))
+        );
+        assert_eq!(
+            Tape::load("1 x").err(),
+            Some(KariuchiError::BadThrow("x".to_string()))
+        );
+    }
+}
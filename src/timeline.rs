@@ -0,0 +1,335 @@
+//! Win probabilities move by move through a game, split into the luck of each throw and
+//! the skill of each move.
+
+use std::fmt::Write;
+
+use crate::{
+    agent,
+    error::KariuchiError,
+    evaluate::Opt,
+    game::{Action, State},
+    record::Record,
+    sticks::Sticks,
+};
+
+/// The first side's win probability around one move.
+#[derive(Debug, Clone)]
+pub struct Point {
+    /// The position the move was played in.
+    pub state: State,
+    /// The move played.
+    pub action: Action,
+    /// Before the throw: the average of `after_throw` over every throw.
+    pub before: f64,
+    /// After the throw, if the mover then plays its best move.
+    pub after_throw: f64,
+    /// After the move that was played.
+    pub after_move: f64,
+}
+
+impl Point {
+    /// Whether the first side made this move.
+    pub fn first(&self) -> bool {
+        self.state.is_first()
+    }
+    // Converts a change for the first side into one for the mover.
+    fn mover(&self, delta: f64) -> f64 {
+        if self.first() {
+            delta
+        } else {
+            -delta
+        }
+    }
+    /// What the throw gained the mover.
+    pub fn luck(&self) -> f64 {
+        self.mover(self.after_throw - self.before)
+    }
+    /// What the move choice gained the mover against its best move; never positive.
+    pub fn skill(&self) -> f64 {
+        self.mover(self.after_move - self.after_throw)
+    }
+}
+
+/// Win probabilities move by move, separating the luck of each throw from the skill of
+/// each move. Moves can be pushed as a game is played or taken from a `Record`.
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    opt: Opt,
+    /// One point per move, in order.
+    pub points: Vec<Point>,
+}
+
+impl Timeline {
+    /// An empty timeline that searches every move with `opt`.
+    pub fn new(opt: Opt) -> Self {
+        Timeline {
+            opt,
+            points: vec![],
+        }
+    }
+    /// Pushes every move of `record`.
+    pub fn from_record(record: &Record, opt: Opt) -> Result<Self, KariuchiError> {
+        let mut t = Timeline::new(opt);
+        for (state, act) in record.states()?.iter().zip(&record.moves) {
+            t.push(state, *act)?;
+        }
+        Ok(t)
+    }
+    /// Evaluates `act` played in `state` and appends it, or fails if `act` is not legal there.
+    pub fn push(&mut self, state: &State, act: Action) -> Result<&Point, KariuchiError> {
+        state.check(act)?;
+        let first = |mover: f64| if state.is_first() { mover } else { 1. - mover };
+        // Every throw is searched so that the value before the throw is their average.
+        let mut before = 0.;
+        let mut after_throw = 0.;
+        let mut after_move = 0.;
+        for (p, sticks) in Sticks::all_sticks() {
+            let actions = state.actions(sticks);
+            let values: Vec<f64> = agent::evaluate_par(state.clone(), &actions, self.opt, 1, false)
+                .iter()
+                .map(|v| v[0])
+                .collect();
+            let best = values.iter().copied().fold(0., f64::max);
+            before += p * best;
+            if sticks == act.sticks() {
+                after_throw = best;
+                if let Some(played) = actions.iter().position(|a| *a == act) {
+                    after_move = values[played];
+                }
+            }
+        }
+        self.points.push(Point {
+            state: state.clone(),
+            action: act,
+            before: first(before),
+            after_throw: first(after_throw),
+            after_move: first(after_move),
+        });
+        Ok(self.points.last().unwrap())
+    }
+    /// Total luck and skill of one side, from its own point of view.
+    pub fn totals(&self, first: bool) -> (f64, f64) {
+        self.points
+            .iter()
+            .filter(|p| p.first() == first)
+            .fold((0., 0.), |(l, s), p| (l + p.luck(), s + p.skill()))
+    }
+    /// One line per move with its values, luck and skill, after a header line.
+    pub fn to_csv(&self) -> String {
+        let mut out =
+            "move,side,sticks,action,before,after_throw,after_move,luck,skill\n".to_string();
+        for (i, p) in self.points.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "{},{},{},\"{}\",{},{},{},{},{}",
+                i + 1,
+                if p.first() { "first" } else { "second" },
+                usize::from(p.action.sticks()),
+                p.state.describe(p.action),
+                p.before,
+                p.after_throw,
+                p.after_move,
+                p.luck(),
+                p.skill()
+            );
+        }
+        out
+    }
+    /// A table of each side's moves and total luck and skill.
+    pub fn summary(&self) -> String {
+        let mut out = format!("{:<8}{:>6}{:>9}{:>9}\n", "", "moves", "luck", "skill");
+        for first in [true, false] {
+            let (luck, skill) = self.totals(first);
+            let moves = self.points.iter().filter(|p| p.first() == first).count();
+            let _ = writeln!(
+                out,
+                "{:<8}{:>6}{:>+9.3}{:>+9.3}",
+                if first { "First" } else { "Second" },
+                moves,
+                luck,
+                skill
+            );
+        }
+        out
+    }
+    /// One column per move with `height` rows between 100% (top) and 0%: `o` marks the
+    /// first side's chances after the move, `.` the span the throw moved them over.
+    pub fn to_ascii(&self, height: usize) -> String {
+        let height = height.max(3);
+        let row = |p: f64| ((1. - p.clamp(0., 1.)) * (height - 1) as f64).round() as usize;
+        let mut grid = vec![vec![' '; self.points.len()]; height];
+        for (x, p) in self.points.iter().enumerate() {
+            let (a, b) = (row(p.before), row(p.after_throw));
+            for y in a.min(b)..=a.max(b) {
+                grid[y][x] = '.';
+            }
+            grid[row(p.after_move)][x] = 'o';
+        }
+        let mut out = String::new();
+        for (y, line) in grid.iter().enumerate() {
+            let label = match y {
+                0 => "100%",
+                _ if y == (height - 1) / 2 && height % 2 == 1 => " 50%",
+                _ if y == height - 1 => "  0%",
+                _ => "",
+            };
+            let line: String = line.iter().collect();
+            let _ = writeln!(out, "{:>4} |{}", label, line.trim_end());
+        }
+        let _ = writeln!(out, "     +{}", "-".repeat(self.points.len()));
+        out
+    }
+    /// A line chart of the first side's chances: each move steps from before the throw
+    /// to after it and after the move, with dots colored by the side that moved.
+    pub fn to_svg(&self) -> String {
+        let (w, h, pad) = ((self.points.len() as f64 * 12.).max(240.) + 60., 240., 30.);
+        let x = |i: f64| pad + i * (w - 2. * pad) / self.points.len().max(1) as f64;
+        let y = |p: f64| pad + (1. - p) * (h - 2. * pad);
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w:.0}" height="{h:.0}" viewBox="0 0 {w:.0} {h:.0}" font-family="sans-serif" font-size="10">"#
+        );
+        let _ = writeln!(
+            out,
+            r##"<rect width="{w:.0}" height="{h:.0}" fill="#fff"/>"##
+        );
+        for (p, label) in [(1., "100%"), (0.5, "50%"), (0., "0%")] {
+            let _ = writeln!(
+                out,
+                r##"<line x1="{pad}" y1="{y:.1}" x2="{:.1}" y2="{y:.1}" stroke="#ccc"/><text x="{:.1}" y="{:.1}" text-anchor="end">{label}</text>"##,
+                w - pad,
+                pad - 4.,
+                y(p) + 3.,
+                y = y(p)
+            );
+        }
+        let mut line = vec![];
+        for (i, p) in self.points.iter().enumerate() {
+            let i = i as f64;
+            line.push(format!("{:.1},{:.1}", x(i), y(p.before)));
+            line.push(format!("{:.1},{:.1}", x(i + 0.5), y(p.after_throw)));
+            line.push(format!("{:.1},{:.1}", x(i + 1.), y(p.after_move)));
+        }
+        let _ = writeln!(
+            out,
+            r##"<polyline points="{}" fill="none" stroke="#555" stroke-width="1.5"/>"##,
+            line.join(" ")
+        );
+        for (i, p) in self.points.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                r##"<circle cx="{:.1}" cy="{:.1}" r="2.5" fill="{}"/>"##,
+                x(i as f64 + 1.),
+                y(p.after_move),
+                if p.first() { "#d33" } else { "#36c" }
+            );
+        }
+        let _ = writeln!(out, "</svg>");
+        out
+    }
+}
+
+const USAGE: &str =
+    "usage: kariuchi-solver timeline [--ply N] [--rollout N] [--csv FILE] [--svg FILE] GAME_FILE";
+
+/// The `timeline` subcommand: charts the win probabilities through the game in GAME_FILE.
+pub fn main(args: &[String]) -> Result<(), String> {
+    let mut opt = Opt {
+        ply: 2,
+        rollout: 20,
+    };
+    let mut csv = None;
+    let mut svg = None;
+    let mut input = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--ply" => opt.ply = value()?.parse().map_err(|e| format!("--ply: {}", e))?,
+            "--rollout" => {
+                opt.rollout = value()?.parse().map_err(|e| format!("--rollout: {}", e))?
+            }
+            "--csv" => csv = Some(value()?.clone()),
+            "--svg" => svg = Some(value()?.clone()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}\n{}", arg, USAGE)),
+            _ => input = Some(arg.clone()),
+        }
+    }
+    if opt.rollout == 0 {
+        return Err("--rollout must be positive".to_string());
+    }
+    let path = input.ok_or(USAGE)?;
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+    let record = Record::load(&text).map_err(|e| format!("{}: {}", path, e))?;
+    let t = Timeline::from_record(&record, opt).map_err(|e| format!("{}: {}", path, e))?;
+    print!("{}\n{}", t.to_ascii(11), t.summary());
+    if let Some(path) = csv {
+        std::fs::write(&path, t.to_csv()).map_err(|e| format!("{}: {}", path, e))?;
+    }
+    if let Some(path) = svg {
+        std::fs::write(&path, t.to_svg()).map_err(|e| format!("{}: {}", path, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tape::Tape;
+    #[test]
+    fn luck_and_skill() {
+        let opt = Opt {
+            ply: 1,
+            rollout: 10,
+        };
+        let mut tape = Tape::seeded(9);
+        let mut state = State::new();
+        let mut t = Timeline::new(opt);
+        for _ in 0..10 {
+            let actions = state.actions(tape.throw().unwrap());
+            let act = *actions.last().unwrap();
+            let p = t.push(&state, act).unwrap();
+            println!(
+                "{} {:.3} {:.3} {:.3}",
+                p.first(),
+                p.before,
+                p.after_throw,
+                p.after_move
+            );
+            assert!(p.skill() <= 1e-12);
+            for v in [p.before, p.after_throw, p.after_move] {
+                assert!((0. ..=1.).contains(&v));
+            }
+            state.act(act);
+        }
+        print!("{}", t.to_ascii(11));
+        print!("{}", t.summary());
+        let csv = t.to_csv();
+        assert_eq!(csv.lines().count(), 11);
+        let (l1, s1) = t.totals(true);
+        let (l2, s2) = t.totals(false);
+        let sum: f64 = t.points.iter().map(|p| p.luck() + p.skill()).sum();
+        assert!((l1 + s1 + l2 + s2 - sum).abs() < 1e-9);
+        assert_eq!(t.to_svg().matches("<circle").count(), 10);
+    }
+    #[test]
+    fn winning_move() {
+        let g = crate::position::Position::goal();
+        let r29 = crate::position::Position::route(29);
+        let state = State::from_pieces([g, g, g, r29], [g, g, g, r29], true);
+        let act = state.actions(Sticks::from(2))[0];
+        let mut t = Timeline::new(Opt { ply: 1, rollout: 5 });
+        let p = t.push(&state, act).unwrap();
+        // Every throw but 1 wins at once.
+        assert_eq!(p.after_throw, 1.);
+        assert_eq!(p.after_move, 1.);
+        assert!(p.before >= 0.75 && p.before < 1.);
+        let wrong = state.actions(Sticks::from(3))[0];
+        let mut end = state.clone();
+        end.act(act);
+        let e = t.push(&end, wrong).unwrap_err();
+        println!("{}", e);
+        assert_eq!(t.points.len(), 1);
+    }
+}
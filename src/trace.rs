@@ -0,0 +1,283 @@
+//! The search tree kept by `Evaluate::trace`, printable as an indented text tree by
+//! `Display` or exported by `to_json` with the `serde` feature.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::{
+    evaluate::{Evaluate, Opt},
+    game::{self, Action, State},
+    sticks::Sticks,
+};
+
+/// A position before a throw, with the best reply to each throw.
+#[derive(Debug, Clone)]
+pub struct Chance {
+    /// The position, before the throw.
+    pub state: State,
+    /// Win probability of the side to move in `state`.
+    pub value: f64,
+    /// One reply per throw, as `Sticks::all_sticks` lists them.
+    pub replies: Vec<Reply>,
+}
+
+/// The move the search expects for one throw at a `Chance` node.
+#[derive(Debug, Clone)]
+pub struct Reply {
+    /// The throw.
+    pub sticks: Sticks,
+    /// Chance of the throw.
+    pub probability: f64,
+    /// The move the search prefers for it.
+    pub best: Action,
+    /// Win probability of the mover after `best`.
+    pub value: f64,
+    /// The position after `best`, unless the game ended or the search stopped there.
+    pub next: Option<Chance>,
+}
+
+/// The root decision for one throw and the expected line after the best move.
+#[derive(Debug, Clone)]
+pub struct Trace {
+    /// The position searched.
+    pub state: State,
+    /// The throw to move with.
+    pub sticks: Sticks,
+    /// Every move with its value for the side to move, best first.
+    pub alternatives: Vec<(Action, f64)>,
+    /// The position after the best move, unless it ends the game.
+    pub line: Option<Chance>,
+    /// Positions searched at each depth in plies below the root, which is depth 0.
+    pub nodes: Vec<usize>,
+}
+
+fn side(state: &State) -> &'static str {
+    if state.is_first() {
+        "first"
+    } else {
+        "second"
+    }
+}
+
+impl Chance {
+    fn write(&self, f: &mut Formatter<'_>, indent: usize) -> fmt::Result {
+        let pad = " ".repeat(indent);
+        writeln!(f, "{}{} to move: {:.4}", pad, side(&self.state), self.value)?;
+        for r in &self.replies {
+            writeln!(
+                f,
+                "{}  {} {:>5.1}%  {:<32} {:.4}",
+                pad,
+                usize::from(r.sticks),
+                r.probability * 100.,
+                self.state.describe(r.best).to_string(),
+                r.value
+            )?;
+            if let Some(next) = &r.next {
+                next.write(f, indent + 4)?;
+            }
+        }
+        Ok(())
+    }
+    #[cfg(feature = "serde")]
+    fn json(&self) -> ChanceJson {
+        ChanceJson {
+            side: side(&self.state),
+            value: self.value,
+            replies: self
+                .replies
+                .iter()
+                .map(|r| ReplyJson {
+                    sticks: usize::from(r.sticks),
+                    probability: r.probability,
+                    best: self.state.describe(r.best).to_string(),
+                    value: r.value,
+                    next: r.next.as_ref().map(|n| n.json()),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Trace {
+    /// The first alternative; `None` only without moves.
+    pub fn best(&self) -> Option<Action> {
+        self.alternatives.first().map(|a| a.0)
+    }
+    /// The alternatives, the nodes per depth and the expected line as one JSON object.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        let trace = TraceJson {
+            side: side(&self.state),
+            sticks: usize::from(self.sticks),
+            alternatives: self
+                .alternatives
+                .iter()
+                .map(|(act, v)| AlternativeJson {
+                    r#move: self.state.describe(*act).to_string(),
+                    value: *v,
+                })
+                .collect(),
+            nodes: self.nodes.clone(),
+            line: self.line.as_ref().map(|c| c.json()),
+        };
+        serde_json::to_string(&trace).expect("trace serializes") + "\n"
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct AlternativeJson {
+    r#move: String,
+    value: f64,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct ReplyJson {
+    sticks: usize,
+    probability: f64,
+    best: String,
+    value: f64,
+    next: Option<ChanceJson>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct ChanceJson {
+    side: &'static str,
+    value: f64,
+    replies: Vec<ReplyJson>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct TraceJson {
+    side: &'static str,
+    sticks: usize,
+    alternatives: Vec<AlternativeJson>,
+    nodes: Vec<usize>,
+    line: Option<ChanceJson>,
+}
+
+impl Display for Trace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} throws {}, {} moves:",
+            side(&self.state),
+            usize::from(self.sticks),
+            self.alternatives.len()
+        )?;
+        for (i, (act, v)) in self.alternatives.iter().enumerate() {
+            writeln!(
+                f,
+                "  {} {:<32} {:.4}",
+                if i == 0 { '*' } else { ' ' },
+                self.state.describe(*act).to_string(),
+                v
+            )?;
+        }
+        let nodes: Vec<String> = self.nodes.iter().map(|n| n.to_string()).collect();
+        writeln!(f, "nodes per depth: {}", nodes.join(" "))?;
+        if let (Some(best), Some(line)) = (self.best(), &self.line) {
+            writeln!(f, "expected line after {}:", self.state.describe(best))?;
+            line.write(f, 2)?;
+        }
+        Ok(())
+    }
+}
+
+/// The `pv` subcommand: shows the search tree behind the choice for a throw in
+/// POSITION_FILE (or stdin).
+pub fn main(args: &[String]) -> Result<(), String> {
+    let mut opt = Opt {
+        ply: 2,
+        rollout: 10,
+    };
+    let mut throw = None;
+    let mut json = false;
+    let mut input = None;
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--ply" => opt.ply = value()?.parse().map_err(|e| format!("--ply: {}", e))?,
+            "--rollout" => {
+                opt.rollout = value()?.parse().map_err(|e| format!("--rollout: {}", e))?
+            }
+            "--sticks" => {
+                let n: usize = value()?.parse().map_err(|e| format!("--sticks: {}", e))?;
+                throw = Some(Sticks::checked(n).map_err(|e| format!("--sticks: {}", e))?);
+            }
+            "--json" => json = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => input = Some(arg.clone()),
+        }
+    }
+    if opt.rollout == 0 {
+        return Err("--rollout must be positive".to_string());
+    }
+    let throw = throw.ok_or("missing --sticks")?;
+    let state = game::read_position(input)?;
+    if state.is_end() {
+        return Err("the game is over".to_string());
+    }
+    let trace = Evaluate::new(opt, state).trace(throw);
+    if json {
+        #[cfg(feature = "serde")]
+        print!("{}", trace.to_json());
+        #[cfg(not(feature = "serde"))]
+        return Err("--json needs the `serde` feature".to_string());
+    } else {
+        print!("{}", trace);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        evaluate::{Evaluate, Opt},
+        game::State,
+        sticks::Sticks,
+    };
+    #[test]
+    fn trace() {
+        // A throw of 4 moves again, so the first side has two pieces to choose from.
+        let mut state = State::new();
+        state.act(state.actions(Sticks::from(4))[0]);
+        let opt = Opt { ply: 1, rollout: 4 };
+        let t = Evaluate::new(opt, state.clone()).trace(Sticks::from(2));
+        print!("{}", t);
+        assert_eq!(t.alternatives.len(), 2);
+        for w in t.alternatives.windows(2) {
+            assert!(w[0].1 >= w[1].1);
+        }
+        // Root, every root move, and every move for every throw after them.
+        assert_eq!(t.nodes[0], 1);
+        assert_eq!(t.nodes[1], t.alternatives.len());
+        assert_eq!(t.nodes.len(), 3);
+        let line = t.line.as_ref().unwrap();
+        assert_eq!(line.replies.len(), Sticks::all_sticks().len());
+        assert!(line.replies.iter().all(|r| r.next.is_none()));
+        let sum: f64 = line.replies.iter().map(|r| r.probability * r.value).sum();
+        assert!((sum - line.value).abs() < 1e-12);
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json() {
+        let mut state = State::new();
+        state.act(state.actions(Sticks::from(4))[0]);
+        let t = Evaluate::new(Opt { ply: 1, rollout: 4 }, state).trace(Sticks::from(2));
+        let json = t.to_json();
+        println!("{}", json);
+        let v: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(v["side"], "first");
+        assert_eq!(v["nodes"][0], 1);
+        assert_eq!(v["alternatives"].as_array().unwrap().len(), 2);
+        assert_eq!(v["alternatives"][0]["value"], t.alternatives[0].1);
+        let replies = v["line"]["replies"].as_array().unwrap();
+        assert_eq!(replies.len(), Sticks::all_sticks().len());
+        assert!(replies.iter().all(|r| r["next"].is_null()));
+    }
+}
@@ -0,0 +1,591 @@
+//! Full-screen terminal UI, enabled by the `tui` feature.
+//!
+//! The human plays the first side against an `agent::engine_from_spec` opponent. The board is
+//! laid out by `position::alignment`, the legal destinations of the current throw are
+//! highlighted, and the side panel shows each move's win probability from `evaluate_par`.
+//! Moves are chosen with the arrow keys and Enter, or by clicking a square.
+//!
+//! Searches run on a worker thread and the event loop picks up their results in `tick`,
+//! so the screen keeps redrawing while the probabilities or the opponent's move are found.
+
+use std::{
+    io,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use rand::Rng;
+use ratatui::{
+    crossterm::{
+        event::{
+            self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind,
+            MouseButton, MouseEventKind,
+        },
+        execute,
+    },
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+use crate::{
+    agent::{self, Agent},
+    evaluate::Opt,
+    game::{Action, State},
+    position::{self, Position, POSITIONS},
+    sticks::Sticks,
+    tape::Tape,
+};
+
+const ROLL_TIME: Duration = Duration::from_millis(600);
+const BOARD_WIDTH: u16 = 46;
+
+// What a worker thread sends back.
+enum Work {
+    // Win probabilities of the human's moves.
+    Probs(Vec<f64>),
+    // The opponent's move, with the opponent handed back.
+    Move(Action, Box<dyn Agent + Send>),
+}
+
+/// The state of the UI: the game, the opponent's search and the move selection.
+pub struct App {
+    state: State,
+    tape: Tape,
+    // Away on the worker thread while it chooses a move.
+    opponent: Option<Box<dyn Agent + Send>>,
+    // The search under way; replaced (and its result dropped) when the throw changes.
+    pending: Option<Receiver<Work>>,
+    opt: Opt,
+    sticks: Option<Sticks>,
+    actions: Vec<Action>,
+    selected: usize,
+    probs: Vec<f64>,
+    history: Vec<String>,
+    rolled: Instant,
+    // Where the board was last drawn, for mouse clicks.
+    board: Rect,
+    quit: bool,
+}
+
+impl App {
+    /// A new game against `opponent` with throws from `tape`; `opt` is the search behind
+    /// the probabilities shown for the human's moves.
+    pub fn new(opponent: Box<dyn Agent + Send>, opt: Opt, tape: Tape) -> Self {
+        let mut app = App {
+            state: State::new(),
+            tape,
+            opponent: Some(opponent),
+            pending: None,
+            opt,
+            sticks: None,
+            actions: vec![],
+            selected: 0,
+            probs: vec![],
+            history: vec![],
+            rolled: Instant::now(),
+            board: Rect::default(),
+            quit: false,
+        };
+        app.roll();
+        app
+    }
+    fn roll(&mut self) {
+        self.sticks = None;
+        self.actions.clear();
+        self.probs.clear();
+        self.pending = None;
+        self.selected = 0;
+        if self.state.is_end() {
+            return;
+        }
+        let Some(sticks) = self.tape.throw() else {
+            return;
+        };
+        self.sticks = Some(sticks);
+        self.actions = self.state.actions(sticks);
+        self.rolled = Instant::now();
+        if self.state.is_first() {
+            let (state, actions, opt) = (self.state.clone(), self.actions.clone(), self.opt);
+            self.spawn(move || {
+                let p = agent::evaluate_par(state, &actions, opt, 1, false);
+                Work::Probs(p.into_iter().map(|v| v[0]).collect())
+            });
+        }
+    }
+    fn spawn(&mut self, f: impl FnOnce() -> Work + Send + 'static) {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            // Nobody listens any more if the throw changed meanwhile.
+            let _ = tx.send(f());
+        });
+        self.pending = Some(rx);
+    }
+    fn thinking(&self) -> bool {
+        self.pending.is_some()
+    }
+    fn rolling(&self) -> bool {
+        self.rolled.elapsed() < ROLL_TIME
+    }
+    fn play(&mut self, act: Action) {
+        let who = if self.state.is_first() { "You" } else { "CPU" };
+        let sticks = usize::from(act.sticks());
+        self.history
+            .push(format!("{} [{}] {}", who, sticks, self.state.describe(act)));
+        self.state.act(act);
+        self.roll();
+    }
+    /// Takes in a finished search, and starts the opponent's once its throw has been shown.
+    pub fn tick(&mut self) {
+        if let Some(rx) = &self.pending {
+            match rx.try_recv() {
+                Ok(Work::Probs(p)) => {
+                    self.pending = None;
+                    self.probs = p;
+                }
+                Ok(Work::Move(act, opponent)) => {
+                    self.pending = None;
+                    self.opponent = Some(opponent);
+                    self.play(act);
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.pending = None,
+            }
+        }
+        if self.state.is_end() || self.state.is_first() || self.rolling() || self.thinking() {
+            return;
+        }
+        if let (Some(sticks), Some(mut opponent)) = (self.sticks, self.opponent.take()) {
+            let (state, actions) = (self.state.clone(), self.actions.clone());
+            self.spawn(move || {
+                let act = opponent.choose(&state, sticks, &actions);
+                Work::Move(act, opponent)
+            });
+        }
+    }
+    fn human_turn(&self) -> bool {
+        !self.state.is_end() && self.state.is_first() && !self.rolling()
+    }
+    /// `q` or Esc quits and `n` starts a new game once one is over. On the human's turn the
+    /// arrow keys select a move and Enter or space plays it.
+    pub fn key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('n') if self.state.is_end() => {
+                self.state = State::new();
+                self.history.clear();
+                self.roll();
+            }
+            _ if !self.human_turn() => {}
+            KeyCode::Up | KeyCode::Left => {
+                self.selected = (self.selected + self.actions.len() - 1) % self.actions.len();
+            }
+            KeyCode::Down | KeyCode::Right => {
+                self.selected = (self.selected + 1) % self.actions.len();
+            }
+            KeyCode::Enter | KeyCode::Char(' ') => self.play(self.actions[self.selected]),
+            _ => {}
+        }
+    }
+    /// Clicking the destination of the selected move plays it. Clicking any other square a
+    /// move starts or ends on selects the next such move.
+    pub fn click(&mut self, column: u16, row: u16) {
+        if !self.human_turn() {
+            return;
+        }
+        let Some(pos) = square_at(self.board, column, row) else {
+            return;
+        };
+        if self.actions.is_empty() {
+            return;
+        }
+        let matching: Vec<usize> = (0..self.actions.len())
+            .filter(|i| self.actions[*i].to() == pos || self.actions[*i].at() == pos)
+            .collect();
+        if matching.is_empty() {
+            return;
+        }
+        if self.actions[self.selected].to() == pos {
+            self.play(self.actions[self.selected]);
+        } else {
+            let next = matching.iter().find(|i| **i > self.selected);
+            self.selected = *next.unwrap_or(&matching[0]);
+        }
+    }
+    /// Draws the board and the side panel, remembering where the board went for `click`.
+    pub fn draw(&mut self, f: &mut Frame) {
+        let [left, right] =
+            Layout::horizontal([Constraint::Length(BOARD_WIDTH + 2), Constraint::Min(30)])
+                .areas(f.area());
+        let (h, _, _) = position::alignment();
+        let [board, sticks, history] = Layout::vertical([
+            Constraint::Length(h as u16 + 2),
+            Constraint::Length(6),
+            Constraint::Min(3),
+        ])
+        .areas(left);
+        let block = Block::default().borders(Borders::ALL).title(self.title());
+        self.board = block.inner(board);
+        f.render_widget(Paragraph::new(self.board_lines()).block(block), board);
+        f.render_widget(
+            Paragraph::new(self.sticks_lines())
+                .block(Block::default().borders(Borders::ALL).title("Throw")),
+            sticks,
+        );
+        let items: Vec<ListItem> = self
+            .history
+            .iter()
+            .rev()
+            .map(|h| ListItem::new(h.as_str()))
+            .collect();
+        f.render_widget(
+            List::new(items).block(Block::default().borders(Borders::ALL).title("History")),
+            history,
+        );
+        let mut list = ListState::default();
+        if self.human_turn() {
+            list.select(Some(self.selected));
+        }
+        f.render_stateful_widget(
+            List::new(self.move_items(right.width.saturating_sub(4) as usize))
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Moves (win probability)"),
+                )
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+            right,
+            &mut list,
+        );
+    }
+    fn title(&self) -> String {
+        if self.state.is_end() {
+            let who = if self.state.is_win_first() {
+                "You win"
+            } else {
+                "CPU wins"
+            };
+            format!("{} - n: new game, q: quit", who)
+        } else if self.state.is_first() {
+            "Your turn - arrows/click, Enter: play, q: quit".to_string()
+        } else if self.thinking() {
+            "CPU is thinking...".to_string()
+        } else {
+            "CPU's turn".to_string()
+        }
+    }
+    fn board_lines(&self) -> Vec<Line<'static>> {
+        let (h, _, alignment) = position::alignment();
+        let mut cells: Vec<Vec<(String, Style)>> =
+            vec![vec![(" ".to_string(), Style::default()); BOARD_WIDTH as usize]; h];
+        let selected = self.selected_action();
+        for i in 0..POSITIONS {
+            let p = Position::from(i);
+            if p == Position::start() || p == Position::goal() {
+                continue;
+            }
+            let (text, mut style) = self.square(p);
+            if self.human_turn() && self.actions.iter().any(|a| a.to() == p) {
+                style = style.bg(Color::Green);
+            }
+            if let Some(a) = selected {
+                if a.at() == p {
+                    style = style.bg(Color::Yellow);
+                } else if a.to() == p {
+                    style = style.bg(Color::Magenta);
+                }
+            }
+            let (y, x) = alignment[i];
+            for (k, c) in text.chars().enumerate() {
+                if let Some(cell) = cells[y].get_mut(x + k) {
+                    *cell = (c.to_string(), style);
+                }
+            }
+        }
+        // Start and goal share a spot in the layout, so they go in the empty middle row.
+        let mut outside = vec![];
+        for (label, p) in [("Start", Position::start()), ("Goal", Position::goal())] {
+            outside.push((format!("{} ", label), Style::default()));
+            outside.push(self.count(p, true));
+            outside.push((" ".to_string(), Style::default()));
+            outside.push(self.count(p, false));
+            outside.push(("   ".to_string(), Style::default()));
+        }
+        let mut lines: Vec<Line> = cells
+            .into_iter()
+            .map(|row| {
+                Line::from(
+                    row.into_iter()
+                        .map(|(c, s)| Span::styled(c, s))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        let middle = h / 2 + 1;
+        lines[middle] = Line::from(
+            outside
+                .into_iter()
+                .map(|(c, s)| Span::styled(c, s))
+                .collect::<Vec<_>>(),
+        );
+        lines
+    }
+    fn count(&self, p: Position, first: bool) -> (String, Style) {
+        let n = self.state.pieces(first).iter().filter(|x| **x == p).count();
+        let (mark, color) = if first {
+            ("O", Color::Red)
+        } else {
+            ("X", Color::Blue)
+        };
+        (format!("{}{}", mark, n), Style::default().fg(color))
+    }
+    fn square(&self, p: Position) -> (String, Style) {
+        let first = self.state.pieces(true).iter().filter(|x| **x == p).count();
+        let second = self.state.pieces(false).iter().filter(|x| **x == p).count();
+        if first > 0 {
+            self.count(p, true)
+        } else if second > 0 {
+            self.count(p, false)
+        } else {
+            (
+                "..".to_string(),
+                Style::default().add_modifier(Modifier::DIM),
+            )
+        }
+    }
+    fn selected_action(&self) -> Option<Action> {
+        if self.human_turn() {
+            self.actions.get(self.selected).copied()
+        } else {
+            None
+        }
+    }
+    fn sticks_lines(&self) -> Vec<Line<'static>> {
+        let Some(sticks) = self.sticks else {
+            return vec![];
+        };
+        // Face-up sticks count the throw; all four face down is a 5.
+        let faces: Vec<bool> = if self.rolling() {
+            let mut rng = rand::thread_rng();
+            (0..4).map(|_| rng.gen()).collect()
+        } else {
+            let up = usize::from(sticks) % 5;
+            (0..4).map(|i| i < up).collect()
+        };
+        let mut lines = vec![];
+        for _ in 0..3 {
+            let spans: Vec<Span> = faces
+                .iter()
+                .map(|up| {
+                    if *up {
+                        Span::styled(" ██ ", Style::default().fg(Color::Yellow))
+                    } else {
+                        Span::styled(" ░░ ", Style::default().fg(Color::DarkGray))
+                    }
+                })
+                .collect();
+            lines.push(Line::from(spans));
+        }
+        let text = if self.rolling() {
+            "throwing...".to_string()
+        } else if sticks.replay() {
+            format!("{} (replay)", usize::from(sticks))
+        } else {
+            format!("{}", usize::from(sticks))
+        };
+        lines.push(Line::from(text));
+        lines
+    }
+    fn move_items(&self, width: usize) -> Vec<ListItem<'static>> {
+        if !self.human_turn() {
+            return vec![];
+        }
+        let bar = width.saturating_sub(30).clamp(5, 30);
+        self.actions
+            .iter()
+            .enumerate()
+            .map(|(i, act)| {
+                let p = self.probs.get(i).copied();
+                let filled = (p.unwrap_or(0.) * bar as f64).round() as usize;
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("{: <24}", self.state.describe(*act).to_string())),
+                    Span::styled("█".repeat(filled), Style::default().fg(Color::Green)),
+                    Span::styled(
+                        "░".repeat(bar - filled),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::raw(p.map_or(" ...".to_string(), |p| format!(" {:0.3}", p))),
+                ]))
+            })
+            .collect()
+    }
+}
+
+/// The position drawn at (`column`, `row`) of the screen when the board is at `board`.
+fn square_at(board: Rect, column: u16, row: u16) -> Option<Position> {
+    if column < board.x || row < board.y {
+        return None;
+    }
+    let (y, x) = ((row - board.y) as usize, (column - board.x) as usize);
+    let (_, _, alignment) = position::alignment();
+    (0..POSITIONS)
+        .map(Position::from)
+        .filter(|p| *p != Position::start() && *p != Position::goal())
+        .find(|p| {
+            let (py, px) = alignment[usize::from(*p)];
+            py == y && px <= x && x < px + 2
+        })
+}
+
+/// Runs `app` in the terminal, with mouse capture, until it quits.
+pub fn run(app: &mut App) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    execute!(io::stdout(), EnableMouseCapture)?;
+    let res = (|| {
+        while !app.quit {
+            terminal.draw(|f| app.draw(f))?;
+            app.tick();
+            if event::poll(Duration::from_millis(50))? {
+                match event::read()? {
+                    Event::Key(k) if k.kind == KeyEventKind::Press => app.key(k.code),
+                    Event::Mouse(m) if m.kind == MouseEventKind::Down(MouseButton::Left) => {
+                        app.click(m.column, m.row)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    })();
+    execute!(io::stdout(), DisableMouseCapture)?;
+    ratatui::restore();
+    res
+}
+
+/// The `tui` subcommand: plays against the engine given as the first argument.
+pub fn main(args: &[String]) -> Result<(), String> {
+    let spec = args
+        .first()
+        .map(|s| s.as_str())
+        .unwrap_or("expectimax:ply=2,rollout=10,tries=1");
+    let opponent = agent::engine_from_spec(spec)?;
+    let opt = Opt {
+        ply: 1,
+        rollout: 10,
+    };
+    let mut app = App::new(opponent, opt, Tape::random());
+    run(&mut app).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::NUM_PIECES;
+    use ratatui::{backend::TestBackend, Terminal};
+
+    fn app() -> App {
+        let opt = Opt { ply: 0, rollout: 2 };
+        let mut app = App::new(Box::new(agent::Random), opt, Tape::seeded(1));
+        app.rolled -= ROLL_TIME;
+        app
+    }
+    // Ticks until the search under way has reported back.
+    fn settle(app: &mut App) {
+        while app.thinking() {
+            app.tick();
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+    fn screen(app: &mut App) -> String {
+        let mut t = Terminal::new(TestBackend::new(100, 30)).unwrap();
+        t.draw(|f| app.draw(f)).unwrap();
+        let buf = t.backend().buffer().clone();
+        let mut s = String::new();
+        for y in 0..buf.area.height {
+            for x in 0..buf.area.width {
+                s += buf[(x, y)].symbol();
+            }
+            s += "\n";
+        }
+        s
+    }
+    #[test]
+    fn draw() {
+        let mut app = app();
+        settle(&mut app);
+        assert_eq!(app.probs.len(), app.actions.len());
+        let s = screen(&mut app);
+        println!("{}", s);
+        assert!(s.contains("Your turn"));
+        assert!(s.contains(&format!("Start O{} X{}", NUM_PIECES, NUM_PIECES)));
+        assert!(s.contains(&app.state.describe(app.actions[0]).to_string()));
+    }
+    #[test]
+    fn click_plays() {
+        let mut app = app();
+        screen(&mut app);
+        let to = app.actions[0].to();
+        let (_, _, alignment) = position::alignment();
+        let (y, x) = alignment[usize::from(to)];
+        let (column, row) = (app.board.x + x as u16, app.board.y + y as u16);
+        assert_eq!(square_at(app.board, column + 1, row), Some(to));
+        app.click(column, row);
+        assert_eq!(app.history.len(), 1);
+        assert!(!app.state.is_first() || app.sticks.unwrap().replay());
+    }
+    #[test]
+    fn click_twice_plays() {
+        let mut app = app();
+        let r = Position::route;
+        let sticks = Sticks::from(2);
+        let s = State::from_pieces(
+            [r(3), Position::start(), Position::start(), r(3)],
+            [Position::start(); NUM_PIECES],
+            true,
+        );
+        app.actions = s.actions(sticks);
+        app.state = s;
+        app.sticks = Some(sticks);
+        app.selected = 0;
+        screen(&mut app);
+        let i = (0..app.actions.len())
+            .find(|i| app.actions[*i].to() != app.actions[0].to())
+            .unwrap();
+        let to = app.actions[i].to();
+        let (_, _, alignment) = position::alignment();
+        let (y, x) = alignment[usize::from(to)];
+        let (column, row) = (app.board.x + x as u16, app.board.y + y as u16);
+        app.click(column, row);
+        assert_eq!(app.selected, i);
+        assert!(app.history.is_empty());
+        app.click(column, row);
+        assert_eq!(app.history.len(), 1);
+        println!("{}", app.history[0]);
+    }
+    #[test]
+    fn keys() {
+        let mut app = app();
+        app.key(KeyCode::Down);
+        app.key(KeyCode::Enter);
+        assert_eq!(app.history.len(), 1);
+        app.key(KeyCode::Char('q'));
+        assert!(app.quit);
+    }
+    #[test]
+    fn opponent_on_worker() {
+        let mut app = app();
+        while app.state.is_first() {
+            app.key(KeyCode::Enter);
+            app.rolled -= ROLL_TIME;
+        }
+        app.tick();
+        assert!(app.thinking() && app.opponent.is_none());
+        println!("{}", app.title());
+        settle(&mut app);
+        assert!(app.opponent.is_some());
+        assert!(app.history.iter().any(|h| h.starts_with("CPU")));
+    }
+}
@@ -0,0 +1,490 @@
+//! WebSocket game server for live games on the LAN, enabled by the `ws` feature.
+//!
+//! The server owns each room's `State` and throws the sticks itself, so clients only
+//! ever pick one of the legal moves it offers. Messages are JSON objects tagged by `type`.
+//!
+//! Client to server:
+//! - `{"type": "join", "room": NAME, "engine": SPEC?, "hint": {"ply", "rollout"}?}` takes the
+//!   first free seat of the room, or watches if both are taken. `engine` and `hint` are only
+//!   read when the room is created: the engine (an `agent::engine_from_spec` spec within
+//!   `MAX_ENGINE`) takes the second seat and `hint` attaches the engine's suggestion to every
+//!   throw a player is to move on. The hint searches at most `MAX_HINT_PLY` deep with
+//!   `MAX_HINT_ROLLOUT` playouts.
+//! - `{"type": "move", "index": N}` plays the N-th of the offered moves.
+//!
+//! Server to client:
+//! - `{"type": "joined", "room", "seat": "first" | "second" | "spectator"}`
+//! - `{"type": "state", ...}` after every change: the position, the `visual` board, the rows
+//!   of it that changed, the current throw with its legal moves, the last move and the hint.
+//! - `{"type": "error", "message"}`
+//!
+//! The engine searches without holding the room, and a room goes away with its last client.
+
+use std::{
+    collections::HashMap,
+    io,
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        mpsc::{self, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tungstenite::Message;
+
+use crate::{
+    agent::{self, Agent},
+    evaluate::Opt,
+    game::{Action, Description, State},
+    sticks::Sticks,
+    tape::Tape,
+};
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Request {
+    Join {
+        room: String,
+        engine: Option<String>,
+        hint: Option<HintOpt>,
+    },
+    Move {
+        index: usize,
+    },
+}
+
+/// Deepest search a hint may ask for; it runs on every throw while the room is locked.
+pub const MAX_HINT_PLY: usize = 2;
+/// Most playouts per leaf a hint may ask for.
+pub const MAX_HINT_ROLLOUT: usize = 100;
+
+/// Largest parameters an engine spec may give, e.g. `expectimax:ply=3,rollout=30`.
+pub const MAX_ENGINE: &[(&str, usize)] = &[
+    ("ply", 3),
+    ("rollout", 30),
+    ("tries", 3),
+    ("iterations", 20000),
+];
+
+#[derive(Deserialize, Clone, Copy)]
+struct HintOpt {
+    ply: usize,
+    rollout: usize,
+}
+
+impl HintOpt {
+    fn capped(self) -> Self {
+        HintOpt {
+            ply: self.ply.min(MAX_HINT_PLY),
+            rollout: self.rollout.clamp(1, MAX_HINT_ROLLOUT),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum SeatName {
+    First,
+    Second,
+    Spectator,
+}
+
+enum Seat {
+    Open,
+    Player(usize),
+    Engine(Box<dyn Agent + Send>),
+    // The engine, while it searches outside the room's lock.
+    Thinking,
+}
+
+// What the engine needs to choose a move away from the room.
+struct EngineTurn {
+    seat: usize,
+    agent: Box<dyn Agent + Send>,
+    state: State,
+    sticks: Sticks,
+    actions: Vec<Action>,
+}
+
+struct Room {
+    state: State,
+    sticks: Option<Sticks>,
+    tape: Tape,
+    seats: [Seat; 2],
+    clients: Vec<(usize, Sender<String>)>,
+    visual: String,
+    last: Option<Description>,
+    hint: Option<HintOpt>,
+}
+
+impl Room {
+    fn new(engine: Option<Box<dyn Agent + Send>>, hint: Option<HintOpt>, tape: Tape) -> Self {
+        let state = State::new();
+        let visual = state.visual();
+        Room {
+            state,
+            sticks: None,
+            tape,
+            seats: [Seat::Open, engine.map_or(Seat::Open, Seat::Engine)],
+            clients: vec![],
+            visual,
+            last: None,
+            hint: hint.map(HintOpt::capped),
+        }
+    }
+    fn join(&mut self, id: usize, tx: Sender<String>) -> SeatName {
+        self.clients.push((id, tx));
+        for (i, seat) in self.seats.iter_mut().enumerate() {
+            if let Seat::Open = seat {
+                *seat = Seat::Player(id);
+                return if i == 0 {
+                    SeatName::First
+                } else {
+                    SeatName::Second
+                };
+            }
+        }
+        SeatName::Spectator
+    }
+    fn leave(&mut self, id: usize) {
+        self.clients.retain(|(c, _)| *c != id);
+        for seat in self.seats.iter_mut() {
+            if let Seat::Player(p) = seat {
+                if *p == id {
+                    *seat = Seat::Open;
+                }
+            }
+        }
+    }
+    fn mover(&self) -> usize {
+        if self.state.is_first() {
+            0
+        } else {
+            1
+        }
+    }
+    fn play(&mut self, id: usize, index: usize) -> Result<(), String> {
+        match self.seats[self.mover()] {
+            Seat::Player(p) if p == id => {}
+            _ => return Err("not your turn".to_string()),
+        }
+        let sticks = self.sticks.ok_or("the game is over")?;
+        let actions = self.state.actions(sticks);
+        let act = *actions
+            .get(index)
+            .ok_or_else(|| format!("no move {}", index))?;
+        self.apply(act);
+        Ok(())
+    }
+    fn apply(&mut self, act: Action) {
+        self.last = Some(self.state.describe(act));
+        self.state.act(act);
+        self.sticks = None;
+    }
+    // Throws for the side to move and broadcasts. If the engine is to move, hands it out to
+    // search; `advance` seats it again with its move.
+    fn next_turn(&mut self) -> Option<EngineTurn> {
+        if !self.state.is_end() && self.sticks.is_none() {
+            self.sticks = self.tape.throw();
+        }
+        let seat = self.mover();
+        let turn = match (self.sticks, &self.seats[seat]) {
+            (Some(sticks), Seat::Engine(_)) if !self.state.is_end() => {
+                let Seat::Engine(agent) = std::mem::replace(&mut self.seats[seat], Seat::Thinking)
+                else {
+                    unreachable!()
+                };
+                Some(EngineTurn {
+                    seat,
+                    agent,
+                    state: self.state.clone(),
+                    sticks,
+                    actions: self.state.actions(sticks),
+                })
+            }
+            _ => None,
+        };
+        self.broadcast();
+        turn
+    }
+    fn hint(&self, sticks: Sticks) -> Option<Value> {
+        let opt = self.hint?;
+        let opt = Opt {
+            ply: opt.ply,
+            rollout: opt.rollout,
+        };
+        let actions = self.state.actions(sticks);
+        let p = agent::evaluate_par(self.state.clone(), &actions, opt, 1, false);
+        let (i, value) = p
+            .iter()
+            .map(|v| v[0])
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+        Some(json!({
+            "index": i,
+            "text": self.state.describe(actions[i]).to_string(),
+            "value": value,
+        }))
+    }
+    fn broadcast(&mut self) {
+        // Only a player gets a hint; the engine's own turns would just pay for it.
+        let hint = match (self.sticks, &self.seats[self.mover()]) {
+            (Some(sticks), Seat::Player(_)) if !self.state.is_end() => self.hint(sticks),
+            _ => None,
+        };
+        let visual = self.state.visual();
+        let diff: Vec<Value> = visual
+            .lines()
+            .zip(self.visual.lines())
+            .enumerate()
+            .filter(|(_, (new, old))| new != old)
+            .map(|(row, (new, _))| json!({ "row": row, "text": new }))
+            .collect();
+        let moves: Vec<Value> = match self.sticks {
+            Some(sticks) if !self.state.is_end() => self
+                .state
+                .actions(sticks)
+                .into_iter()
+                .map(|act| json!(self.state.describe(act).to_string()))
+                .collect(),
+            _ => vec![],
+        };
+        let winner = match (self.state.is_end(), self.state.is_win_first()) {
+            (false, _) => Value::Null,
+            (true, true) => json!("first"),
+            (true, false) => json!("second"),
+        };
+        let msg = json!({
+            "type": "state",
+            "position": self.state.to_string(),
+            "state": self.state,
+            "visual": visual,
+            "diff": diff,
+            "turn": if self.state.is_first() { "first" } else { "second" },
+            "winner": winner,
+            "sticks": self.sticks,
+            "moves": moves,
+            "last": self.last.map(|d| d.to_string()),
+            "hint": hint,
+        })
+        .to_string();
+        self.visual = visual;
+        self.clients.retain(|(_, tx)| tx.send(msg.clone()).is_ok());
+    }
+}
+
+// Lets the engine play its turns, searching without holding the room's lock.
+fn advance(room: &Mutex<Room>) {
+    let mut turn = room.lock().unwrap().next_turn();
+    while let Some(EngineTurn {
+        seat,
+        mut agent,
+        state,
+        sticks,
+        actions,
+    }) = turn
+    {
+        let act = agent.choose(&state, sticks, &actions);
+        let mut r = room.lock().unwrap();
+        r.seats[seat] = Seat::Engine(agent);
+        r.apply(act);
+        turn = r.next_turn();
+    }
+}
+
+type Rooms = Arc<Mutex<HashMap<String, Arc<Mutex<Room>>>>>;
+
+/// The game server. `run` accepts connections until the process ends.
+pub struct GameServer {
+    listener: TcpListener,
+    rooms: Rooms,
+    seed: Option<u64>,
+}
+
+impl GameServer {
+    /// Listens on `addr`, e.g. `0.0.0.0:9001`; port 0 picks a free port.
+    pub fn bind(addr: &str) -> Result<Self, String> {
+        let listener = TcpListener::bind(addr).map_err(|e| format!("{}: {}", addr, e))?;
+        Ok(GameServer {
+            listener,
+            rooms: Arc::new(Mutex::new(HashMap::new())),
+            seed: None,
+        })
+    }
+    /// Every room throws from `Tape::seeded(seed)` instead of a random tape.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+    /// The address the server listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.listener.local_addr().unwrap()
+    }
+    /// Serves each connection on its own thread until the process ends.
+    pub fn run(&self) {
+        for (id, stream) in self.listener.incoming().enumerate() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let rooms = self.rooms.clone();
+            let seed = self.seed;
+            std::thread::spawn(move || connection(id, stream, rooms, seed));
+        }
+    }
+}
+
+fn error(msg: impl ToString) -> String {
+    json!({ "type": "error", "message": msg.to_string() }).to_string()
+}
+
+fn connection(id: usize, stream: TcpStream, rooms: Rooms, seed: Option<u64>) {
+    let Ok(mut ws) = tungstenite::accept(stream) else {
+        return;
+    };
+    // Poll the socket so that broadcasts from other connections get through between reads.
+    if ws
+        .get_ref()
+        .set_read_timeout(Some(Duration::from_millis(20)))
+        .is_err()
+    {
+        return;
+    }
+    let (tx, rx) = mpsc::channel::<String>();
+    let mut room: Option<(String, Arc<Mutex<Room>>)> = None;
+    'conn: loop {
+        while let Ok(msg) = rx.try_recv() {
+            if ws.send(Message::Text(msg)).is_err() {
+                break 'conn;
+            }
+        }
+        let text = match ws.read() {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) => break,
+            Ok(_) => continue,
+            Err(tungstenite::Error::Io(e))
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                continue
+            }
+            Err(_) => break,
+        };
+        // Whether the room changed and the game goes on from there.
+        let mut changed = false;
+        let reply = match serde_json::from_str::<Request>(&text) {
+            Err(e) => Some(error(e)),
+            Ok(Request::Join { .. }) if room.is_some() => Some(error("already in a room")),
+            Ok(Request::Join {
+                room: name,
+                engine,
+                hint,
+            }) => match join(&rooms, &name, engine, hint, seed, id, tx.clone()) {
+                Ok(r) => {
+                    room = Some((name, r));
+                    changed = true;
+                    None
+                }
+                Err(e) => Some(error(e)),
+            },
+            Ok(Request::Move { index }) => match &room {
+                None => Some(error("join a room first")),
+                Some((_, r)) => match r.lock().unwrap().play(id, index) {
+                    Ok(()) => {
+                        changed = true;
+                        None
+                    }
+                    Err(e) => Some(error(e)),
+                },
+            },
+        };
+        if let Some(reply) = reply {
+            if ws.send(Message::Text(reply)).is_err() {
+                break;
+            }
+        }
+        if let (true, Some((_, r))) = (changed, &room) {
+            advance(r);
+        }
+    }
+    if let Some((name, r)) = room {
+        leave(&rooms, &name, &r, id);
+    }
+}
+
+// Takes `id` out of the room, and the room out of `rooms` once nobody is left in it.
+fn leave(rooms: &Rooms, name: &str, room: &Arc<Mutex<Room>>, id: usize) {
+    let mut rooms = rooms.lock().unwrap();
+    let mut r = room.lock().unwrap();
+    r.leave(id);
+    if r.clients.is_empty() && rooms.get(name).is_some_and(|x| Arc::ptr_eq(x, room)) {
+        rooms.remove(name);
+    }
+}
+
+fn join(
+    rooms: &Rooms,
+    name: &str,
+    engine: Option<String>,
+    hint: Option<HintOpt>,
+    seed: Option<u64>,
+    id: usize,
+    tx: Sender<String>,
+) -> Result<Arc<Mutex<Room>>, String> {
+    // Hold `rooms` until seated so that `leave` cannot drop the room in between.
+    let mut rooms = rooms.lock().unwrap();
+    let room = match rooms.get(name) {
+        Some(r) => r.clone(),
+        None => {
+            let engine = engine.map(|s| engine_of(&s)).transpose()?;
+            let tape = seed.map_or_else(Tape::random, Tape::seeded);
+            let r = Arc::new(Mutex::new(Room::new(engine, hint, tape)));
+            rooms.insert(name.to_string(), r.clone());
+            r
+        }
+    };
+    let seat = room.lock().unwrap().join(id, tx.clone());
+    drop(rooms);
+    let _ = tx.send(json!({ "type": "joined", "room": name, "seat": seat }).to_string());
+    Ok(room)
+}
+
+// An engine within `MAX_ENGINE`; a human one would read its moves from the server's stdin.
+fn engine_of(spec: &str) -> Result<Box<dyn Agent + Send>, String> {
+    let args = spec.split_once(':').map_or("", |(_, args)| args);
+    for (k, v) in args.split(',').filter_map(|kv| kv.split_once('=')) {
+        let max = MAX_ENGINE.iter().find(|(key, _)| *key == k);
+        if let (Some((_, max)), Ok(v)) = (max, v.parse::<usize>()) {
+            if v > *max {
+                return Err(format!("engine `{}` is at most {}", k, max));
+            }
+        }
+    }
+    agent::engine_from_spec(spec)
+}
+
+/// The `ws` subcommand: serves live games on every interface, at `--port` or 9001, until
+/// the process ends.
+pub fn main(args: &[String]) -> Result<(), String> {
+    let mut addr = "0.0.0.0:9001".to_string();
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--port" => {
+                let port = it.next().ok_or("--port needs a value")?;
+                addr = format!("0.0.0.0:{}", port);
+            }
+            _ => {
+                return Err(format!(
+                    "unknown option {}\nusage: kariuchi-solver ws [--port P]",
+                    arg
+                ))
+            }
+        }
+    }
+    let server = GameServer::bind(&addr)?;
+    println!("listening on ws://{}", server.addr());
+    server.run();
+    Ok(())
+}
@@ -0,0 +1,77 @@
+//! Zobrist keys for `State::key`.
+//!
+//! A square holding `count` pieces of one side contributes a single key for that
+//! (side, square, count), so it does not matter which pieces stand where. The key of a
+//! position XORs those of every occupied square, plus `second_to_move` on the second
+//! side's turn.
+
+use crate::{
+    game::NUM_PIECES,
+    position::{Position, POSITIONS},
+};
+
+const KEYS: usize = 2 * POSITIONS * (NUM_PIECES + 1) + 1;
+
+const fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+static TABLE: [u64; KEYS] = {
+    let mut t = [0; KEYS];
+    let mut i = 0;
+    while i < KEYS {
+        t[i] = splitmix64(i as u64 + 1);
+        i += 1;
+    }
+    t
+};
+
+/// The key of `count` pieces of one side on `square`; zero pieces have key 0.
+pub fn piece(first: bool, square: Position, count: usize) -> u64 {
+    if count == 0 {
+        return 0;
+    }
+    debug_assert!(count <= NUM_PIECES);
+    let side = if first { 0 } else { 1 };
+    TABLE[(side * POSITIONS + usize::from(square)) * (NUM_PIECES + 1) + count]
+}
+
+/// The key XORed in while the second side is to move.
+pub fn second_to_move() -> u64 {
+    TABLE[KEYS - 1]
+}
+
+/// The key computed from scratch for the given pieces and side to move.
+pub fn key(first_pos: &[Position], second_pos: &[Position], first_turn: bool) -> u64 {
+    let mut k = if first_turn { 0 } else { second_to_move() };
+    for (first, pieces) in [(true, first_pos), (false, second_pos)] {
+        let mut counts = [0; POSITIONS];
+        for p in pieces {
+            counts[usize::from(*p)] += 1;
+        }
+        for (i, c) in counts.iter().enumerate() {
+            k ^= piece(first, Position::from(i), *c);
+        }
+    }
+    k
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn distinct() {
+        let mut keys = TABLE.to_vec();
+        keys.sort_unstable();
+        keys.dedup();
+        assert_eq!(keys.len(), KEYS);
+        let s = Position::start();
+        let g = Position::goal();
+        assert_eq!(key(&[s, g], &[g, g], true), key(&[g, s], &[g, g], true));
+        assert_ne!(key(&[s, g], &[g, g], true), key(&[s, g], &[g, g], false));
+        assert_ne!(key(&[s, g], &[g, g], true), key(&[g, g], &[s, g], true));
+    }
+}
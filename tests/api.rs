@@ -0,0 +1,113 @@
+use kariuchi_solver::{
+    agent::{self, Greedy, Random},
+    evaluate::{Evaluate, Opt},
+    game::{Path, State, NUM_PIECES},
+    position::{HumanPosition, Position, POSITIONS},
+    sticks::Sticks,
+    tape::Tape,
+};
+
+#[test]
+fn seeded_game_is_reproducible() {
+    let play = || {
+        let mut tape = Tape::seeded(11);
+        let s = agent::play(
+            State::new(),
+            &mut Greedy::default(),
+            &mut Greedy::default(),
+            &mut tape,
+        );
+        (s, tape.save())
+    };
+    let (a, tape_a) = play();
+    let (b, tape_b) = play();
+    assert!(a.is_end());
+    assert_eq!(a, b);
+    assert_eq!(tape_a, tape_b);
+}
+
+#[test]
+fn replay_from_saved_tape() {
+    let mut tape = Tape::seeded(2);
+    let s = agent::play(
+        State::new(),
+        &mut Greedy::default(),
+        &mut Greedy::default(),
+        &mut tape,
+    );
+    let mut loaded = Tape::load(&tape.save()).unwrap();
+    let t = agent::play(
+        State::new(),
+        &mut Greedy::default(),
+        &mut Greedy::default(),
+        &mut loaded,
+    );
+    assert_eq!(s, t);
+    assert_eq!(loaded.throw(), None);
+}
+
+#[test]
+fn random_game_ends() {
+    let mut tape = Tape::random();
+    let s = agent::play(State::new(), &mut Random, &mut Random, &mut tape);
+    assert!(s.is_end());
+}
+
+#[test]
+fn positions_round_trip() {
+    for i in 0..POSITIONS {
+        let p = Position::from(i);
+        let name = p.to_string();
+        assert_eq!(name.parse::<Position>().unwrap(), p);
+        let human: HumanPosition = p.into();
+        if let HumanPosition::SHORTCUT(path, x) = human {
+            assert_eq!(Position::shortcut(path, x), p);
+        }
+    }
+    assert_eq!("R5".parse::<Position>().unwrap(), Position::route(5));
+    assert_eq!("C".parse::<Position>().unwrap(), Position::center());
+}
+
+#[test]
+fn action_accessors() {
+    let s = State::new();
+    let act = s.actions(Sticks::from(2))[0];
+    assert_eq!(act.at(), Position::start());
+    assert_eq!(act.to(), Position::route(2));
+    assert_eq!(act.num(), 1);
+    assert_eq!(act.sticks(), Sticks::from(2));
+}
+
+#[test]
+fn state_text_round_trip() {
+    let mut s = State::new();
+    s.act(s.actions(Sticks::from(4))[0]);
+    s.act(s.actions(Sticks::from(1))[0]);
+    let parsed: State = s.to_string().parse().unwrap();
+    assert_eq!(parsed, s);
+    assert_eq!(s.pieces(true).len(), NUM_PIECES);
+}
+
+#[test]
+fn evaluate_finished_race() {
+    let goal = [Position::goal(); NUM_PIECES];
+    let mut first = goal;
+    first[0] = Position::route(30);
+    let s = State::from_pieces(first, [Position::start(); NUM_PIECES], true);
+    let p = Evaluate::new(Opt { ply: 1, rollout: 1 }, s).evaluate();
+    assert_eq!(p, 1.0);
+}
+
+#[test]
+fn describe_capture() {
+    let mut s = State::new();
+    s.act(s.actions(Sticks::from(2))[0]);
+    let act = s.actions(Sticks::from(2))[0];
+    let d = s.describe(act);
+    assert_eq!(d.action, act);
+    assert_eq!(d.path, Path::Route);
+    assert_eq!(d.captured, 1);
+    assert_eq!(d.finished, 0);
+    assert!(d.passes_turn);
+    assert_eq!(d.to_string(), "S -> R2, captures 1");
+}
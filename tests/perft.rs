@@ -0,0 +1,66 @@
+//! Reference move-path counts. A change to these numbers means the rules changed.
+
+use kariuchi_solver::{
+    game::State,
+    perft::{perft, Counts},
+    position::Position,
+};
+
+// (nodes, captures, goals, replays, wins) for depth 1, 2, ...
+fn check(state: &State, expect: &[(u64, u64, u64, u64, u64)]) {
+    for (i, e) in expect.iter().enumerate() {
+        let c = perft(state, i + 1);
+        println!("{} {:?}", i + 1, c);
+        let want = Counts {
+            nodes: e.0,
+            captures: e.1,
+            goals: e.2,
+            replays: e.3,
+            wins: e.4,
+        };
+        assert_eq!(c, want, "depth {}", i + 1);
+    }
+}
+
+#[test]
+fn start() {
+    check(
+        &State::new(),
+        &[
+            (5, 0, 0, 2, 0),
+            (40, 3, 0, 16, 0),
+            (380, 31, 2, 152, 0),
+            (4220, 382, 25, 1688, 0),
+            (52465, 4995, 392, 20986, 0),
+        ],
+    );
+}
+
+#[test]
+fn middle_game() {
+    // Pieces on the route, on shortcuts, in the center, stacked and at the goal.
+    let state = State::from_pieces(
+        [
+            Position::route(3),
+            Position::route(3),
+            Position::shortcut(1, 1),
+            Position::goal(),
+        ],
+        [
+            Position::start(),
+            Position::route(6),
+            Position::center(),
+            Position::shortcut(4, 0),
+        ],
+        true,
+    );
+    check(
+        &state,
+        &[
+            (15, 4, 0, 6, 0),
+            (330, 24, 16, 132, 0),
+            (6755, 711, 262, 2702, 0),
+            (135125, 12309, 5674, 54050, 0),
+        ],
+    );
+}
@@ -0,0 +1,134 @@
+//! Rule invariants checked on random reachable positions.
+
+use kariuchi_solver::{
+    game::{Action, State, NUM_PIECES},
+    position::{Position, POSITIONS},
+    sticks::Sticks,
+};
+use proptest::prelude::*;
+
+// A throw and which of its moves to play, as indices into the legal ones.
+fn plays() -> impl Strategy<Value = Vec<(usize, usize)>> {
+    prop::collection::vec((0..5usize, any::<usize>()), 0..200)
+}
+
+fn throw(i: usize) -> Sticks {
+    Sticks::all_sticks()[i].1
+}
+
+// Plays `plays` from the start until the game ends.
+fn reach(plays: &[(usize, usize)]) -> State {
+    let mut s = State::new();
+    for (t, m) in plays {
+        if s.is_end() {
+            break;
+        }
+        let actions = s.actions(throw(*t));
+        s.act(actions[m % actions.len()]);
+    }
+    s
+}
+
+fn count(s: &State, first: bool, p: Position) -> usize {
+    s.pieces(first).iter().filter(|q| **q == p).count()
+}
+
+proptest! {
+    #[test]
+    fn pieces_stay_on_the_board(plays in plays()) {
+        let s = reach(&plays);
+        for first in [true, false] {
+            let mut total = 0;
+            for i in 0..POSITIONS {
+                total += count(&s, first, Position::from(i));
+            }
+            prop_assert_eq!(total, NUM_PIECES);
+        }
+    }
+
+    #[test]
+    fn winner_iff_a_side_is_home(plays in plays()) {
+        let s = reach(&plays);
+        let home = |first| count(&s, first, Position::goal()) == NUM_PIECES;
+        prop_assert_eq!(s.is_end(), home(true) || home(false));
+        if s.is_end() {
+            prop_assert_eq!(s.is_win_first(), home(true));
+        }
+    }
+
+    #[test]
+    fn moves(plays in plays(), t in 0..5usize, m in any::<usize>()) {
+        let s = reach(&plays);
+        prop_assume!(!s.is_end());
+        let sticks = throw(t);
+        let actions = s.actions(sticks);
+        prop_assert!(!actions.is_empty());
+        for act in &actions {
+            prop_assert_ne!(act.at(), Position::goal());
+            prop_assert!(act.num() >= 1 && act.num() <= count(&s, s.is_first(), act.at()));
+        }
+        let act = actions[m % actions.len()];
+        let me = s.is_first();
+        let mut after = s.clone();
+        after.act(act);
+
+        // The turn passes unless the throw lets the mover go again.
+        prop_assert_eq!(after.is_first() != me, !sticks.replay());
+
+        // Pieces at the goal stay there.
+        prop_assert!(count(&after, me, Position::goal()) >= count(&s, me, Position::goal()));
+        prop_assert_eq!(
+            count(&after, !me, Position::goal()),
+            count(&s, !me, Position::goal())
+        );
+
+        // Exactly `num` pieces travel from `at` to `to`.
+        for i in 0..POSITIONS {
+            let p = Position::from(i);
+            let expect = count(&s, me, p) + usize::from(p == act.to()) * act.num()
+                - usize::from(p == act.at()) * act.num();
+            prop_assert_eq!(count(&after, me, p), expect);
+        }
+
+        // Exactly the opposing pieces on `to` go back to the start, except at the goal.
+        let captured = if act.to() == Position::goal() {
+            0
+        } else {
+            count(&s, !me, act.to())
+        };
+        for i in 0..POSITIONS {
+            let p = Position::from(i);
+            let expect = if p == act.to() && captured > 0 {
+                0
+            } else if p == Position::start() {
+                count(&s, !me, p) + captured
+            } else {
+                count(&s, !me, p)
+            };
+            prop_assert_eq!(count(&after, !me, p), expect);
+        }
+        prop_assert_eq!(s.describe(act).captured, captured);
+    }
+
+    #[test]
+    fn legal_iff_offered(
+        plays in plays(),
+        t in 0..5usize,
+        at in 0..POSITIONS,
+        to in 0..POSITIONS,
+        num in 1..=NUM_PIECES,
+    ) {
+        let s = reach(&plays);
+        let sticks = throw(t);
+        let Ok(act) = Action::try_new(Position::from(at), Position::from(to), num, sticks) else {
+            return Ok(());
+        };
+        let offered = !s.is_end() && s.actions(sticks).contains(&act);
+        prop_assert_eq!(s.is_legal(act), offered);
+        let mut after = s.clone();
+        prop_assert_eq!(after.try_act(act).is_ok(), offered);
+        if !offered {
+            prop_assert_eq!(&after, &s);
+        }
+    }
+}
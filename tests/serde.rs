@@ -0,0 +1,113 @@
+#![cfg(feature = "serde")]
+
+use kariuchi_solver::{
+    agent::{self, Greedy},
+    game::{Action, State, NUM_PIECES},
+    position::{Position, POSITIONS},
+    sticks::Sticks,
+    tape::Tape,
+};
+
+fn states() -> Vec<State> {
+    let mut tape = Tape::seeded(1);
+    let mut s = State::new();
+    let mut v = vec![s.clone()];
+    while !s.is_end() {
+        let sticks = tape.throw().unwrap();
+        let actions = s.actions(sticks);
+        s.act(agent::Agent::choose(
+            &mut Greedy::default(),
+            &s,
+            sticks,
+            &actions,
+        ));
+        v.push(s.clone());
+    }
+    v
+}
+
+#[test]
+fn position_by_name() {
+    assert_eq!(
+        serde_json::to_string(&Position::shortcut(2, 1)).unwrap(),
+        "\"K2_1\""
+    );
+    for i in 0..POSITIONS {
+        let p = Position::from(i);
+        let json = serde_json::to_string(&p).unwrap();
+        assert_eq!(serde_json::from_str::<Position>(&json).unwrap(), p);
+    }
+    assert!(serde_json::from_str::<Position>("\"R31\"").is_err());
+    assert!(serde_json::from_str::<Position>("3").is_err());
+}
+
+#[test]
+fn sticks() {
+    for (_, s) in Sticks::all_sticks() {
+        let json = serde_json::to_string(&s).unwrap();
+        assert_eq!(serde_json::from_str::<Sticks>(&json).unwrap(), s);
+    }
+    assert!(serde_json::from_str::<Sticks>("0").is_err());
+    assert!(serde_json::from_str::<Sticks>("6").is_err());
+}
+
+#[test]
+fn state_json_round_trip() {
+    for s in states() {
+        let json = serde_json::to_string(&s).unwrap();
+        assert_eq!(serde_json::from_str::<State>(&json).unwrap(), s);
+    }
+    let json = serde_json::to_string(&State::new()).unwrap();
+    assert_eq!(
+        json,
+        r#"{"first":["S","S","S","S"],"second":["S","S","S","S"],"first_turn":true,"winner":null}"#
+    );
+}
+
+#[test]
+fn state_bincode_round_trip() {
+    for s in states() {
+        let bytes = bincode::serialize(&s).unwrap();
+        assert_eq!(bincode::deserialize::<State>(&bytes).unwrap(), s);
+    }
+}
+
+#[test]
+fn action_round_trip() {
+    let s = &states()[3];
+    for (_, sticks) in Sticks::all_sticks() {
+        for act in s.actions(sticks) {
+            let json = serde_json::to_string(&act).unwrap();
+            assert_eq!(serde_json::from_str::<Action>(&json).unwrap(), act);
+            let bytes = bincode::serialize(&act).unwrap();
+            assert_eq!(bincode::deserialize::<Action>(&bytes).unwrap(), act);
+        }
+    }
+}
+
+#[test]
+fn rejects_invalid_state() {
+    let three =
+        r#"{"first":["S","S","S"],"second":["S","S","S","S"],"first_turn":true,"winner":null}"#;
+    assert!(serde_json::from_str::<State>(three).is_err());
+    let square =
+        r#"{"first":["S","S","S","Q"],"second":["S","S","S","S"],"first_turn":true,"winner":null}"#;
+    assert!(serde_json::from_str::<State>(square).is_err());
+    let winner = r#"{"first":["S","S","S","S"],"second":["S","S","S","S"],"first_turn":true,"winner":"first"}"#;
+    assert!(serde_json::from_str::<State>(winner).is_err());
+    let goal = vec!["G"; NUM_PIECES];
+    let done = format!(
+        r#"{{"first":{},"second":["S","S","S","S"],"first_turn":false,"winner":null}}"#,
+        serde_json::to_string(&goal).unwrap()
+    );
+    assert!(serde_json::from_str::<State>(&done).is_err());
+    let won = done.replace("null", "\"first\"");
+    assert!(serde_json::from_str::<State>(&won).unwrap().is_win_first());
+}
+
+#[test]
+fn rejects_invalid_action() {
+    assert!(serde_json::from_str::<Action>(r#"{"at":"S","to":"R1","num":0,"sticks":1}"#).is_err());
+    assert!(serde_json::from_str::<Action>(r#"{"at":"G","to":"R1","num":1,"sticks":1}"#).is_err());
+    assert!(serde_json::from_str::<Action>(r#"{"at":"S","to":"R1","num":1,"sticks":1}"#).is_ok());
+}
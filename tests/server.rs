@@ -0,0 +1,244 @@
+#![cfg(feature = "server")]
+
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    thread,
+    time::{Duration, Instant},
+};
+
+use kariuchi_solver::{
+    game::State,
+    server::{Server, MAX_PLY, MAX_ROLLOUT, MAX_SEARCHES},
+};
+use serde_json::{json, Value};
+
+fn start() -> (Server, SocketAddr) {
+    let server = Server::bind("127.0.0.1:0").unwrap();
+    let addr = server.addr();
+    let s = server.clone();
+    thread::spawn(move || s.run());
+    (server, addr)
+}
+
+fn request(addr: SocketAddr, method: &str, path: &str, body: Option<Value>) -> (u16, Value) {
+    let body = body.map(|b| b.to_string()).unwrap_or_default();
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut res = String::new();
+    stream.read_to_string(&mut res).unwrap();
+    let (head, body) = res.split_once("\r\n\r\n").unwrap();
+    let code = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+    (code, serde_json::from_str(body).unwrap())
+}
+
+fn start_position() -> String {
+    State::new().to_string()
+}
+
+#[test]
+fn moves_and_apply() {
+    let (server, addr) = start();
+    let (code, res) = request(
+        addr,
+        "POST",
+        "/moves",
+        Some(json!({ "position": start_position(), "sticks": 3 })),
+    );
+    assert_eq!(code, 200);
+    let moves = res["moves"].as_array().unwrap();
+    assert_eq!(moves.len(), 1);
+    assert_eq!(moves[0]["text"], "S -> R3");
+
+    let action = moves[0]["action"].clone();
+    let (code, res) = request(
+        addr,
+        "POST",
+        "/apply",
+        Some(json!({ "position": start_position(), "action": action })),
+    );
+    assert_eq!(code, 200);
+    let after: State = res["position"].as_str().unwrap().parse().unwrap();
+    assert!(!after.is_first());
+
+    let illegal = json!({ "at": "S", "to": "R4", "num": 1, "sticks": 3 });
+    let (code, res) = request(
+        addr,
+        "POST",
+        "/apply",
+        Some(json!({ "position": start_position(), "action": illegal })),
+    );
+    assert_eq!(code, 400);
+    assert!(res["error"].as_str().unwrap().contains("illegal"));
+    server.stop();
+}
+
+#[test]
+fn bad_requests() {
+    let (server, addr) = start();
+    let (code, _) = request(
+        addr,
+        "POST",
+        "/moves",
+        Some(json!({ "position": "nonsense", "sticks": 3 })),
+    );
+    assert_eq!(code, 400);
+    let (code, _) = request(addr, "GET", "/nowhere", None);
+    assert_eq!(code, 404);
+    let (code, _) = request(addr, "GET", "/jobs/42", None);
+    assert_eq!(code, 404);
+    server.stop();
+}
+
+#[test]
+fn evaluate_and_best() {
+    let (server, addr) = start();
+    let (code, res) = request(
+        addr,
+        "POST",
+        "/evaluate",
+        Some(json!({ "position": start_position(), "ply": 1, "rollout": 2 })),
+    );
+    assert_eq!(code, 200);
+    assert_eq!(res["ply"], 1);
+    let v = res["value"].as_f64().unwrap();
+    assert!((0. ..=1.).contains(&v));
+
+    let (code, res) = request(
+        addr,
+        "POST",
+        "/best",
+        Some(json!({ "position": start_position(), "sticks": 2, "ply": 1, "rollout": 2 })),
+    );
+    assert_eq!(code, 200);
+    assert_eq!(res["text"], "S -> R2");
+    assert_eq!(res["alternatives"].as_array().unwrap().len(), 1);
+    server.stop();
+}
+
+#[test]
+fn time_limit() {
+    let (server, addr) = start();
+    // The deepest allowed search takes far longer than the limit.
+    let begin = Instant::now();
+    let (code, res) = request(
+        addr,
+        "POST",
+        "/evaluate",
+        Some(json!({
+            "position": start_position(),
+            "ply": MAX_PLY,
+            "rollout": 50,
+            "time_limit_ms": 500,
+        })),
+    );
+    println!("{:?} {}", begin.elapsed(), res);
+    assert_eq!(code, 200);
+    assert!(res["ply"].as_u64().unwrap() < MAX_PLY as u64);
+    assert!(begin.elapsed() < Duration::from_secs(5));
+    server.stop();
+}
+
+#[test]
+fn busy() {
+    let (server, addr) = start();
+    let heavy = json!({
+        "kind": "evaluate",
+        "position": start_position(),
+        "ply": MAX_PLY,
+        "rollout": MAX_ROLLOUT,
+    });
+    let ids: Vec<u64> = (0..MAX_SEARCHES)
+        .map(|_| {
+            let (code, res) = request(addr, "POST", "/jobs", Some(heavy.clone()));
+            assert_eq!(code, 200);
+            res["id"].as_u64().unwrap()
+        })
+        .collect();
+    let (code, _) = request(addr, "POST", "/jobs", Some(heavy.clone()));
+    assert_eq!(code, 429);
+    let (code, res) = request(
+        addr,
+        "POST",
+        "/evaluate",
+        Some(json!({ "position": start_position(), "ply": 0, "rollout": 1 })),
+    );
+    assert_eq!(code, 429);
+    println!("{}", res);
+    for id in ids {
+        request(addr, "DELETE", &format!("/jobs/{}", id), None);
+    }
+    server.stop();
+}
+
+#[test]
+fn jobs() {
+    let (server, addr) = start();
+    let (code, res) = request(
+        addr,
+        "POST",
+        "/jobs",
+        Some(json!({ "kind": "evaluate", "position": start_position(), "ply": 0, "rollout": 4 })),
+    );
+    assert_eq!(code, 200);
+    let id = res["id"].as_u64().unwrap();
+    let mut status = json!(null);
+    for _ in 0..100 {
+        let (_, res) = request(addr, "GET", &format!("/jobs/{}", id), None);
+        status = res;
+        if status["status"] != "running" {
+            break;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    assert_eq!(status["status"], "done");
+    assert_eq!(status["result"]["ply"], 0);
+
+    let (_, res) = request(
+        addr,
+        "POST",
+        "/jobs",
+        Some(json!({ "kind": "best", "position": start_position(), "sticks": 1, "ply": 30 })),
+    );
+    let id = res["id"].as_u64().unwrap();
+    let (code, res) = request(addr, "DELETE", &format!("/jobs/{}", id), None);
+    assert_eq!(code, 200);
+    assert_eq!(res["status"], "cancelled");
+    let (_, res) = request(addr, "GET", &format!("/jobs/{}", id), None);
+    assert_eq!(res["status"], "cancelled");
+    server.stop();
+}
+
+#[test]
+fn finished_and_capped() {
+    let (server, addr) = start();
+    let end = "end: winner First, turn: second\nG G G G / R29 K5_2 S S";
+    let (code, res) = request(
+        addr,
+        "POST",
+        "/moves",
+        Some(json!({ "position": end, "sticks": 1 })),
+    );
+    assert_eq!(code, 200);
+    assert_eq!(res["moves"], json!([]));
+
+    // Without a time limit the search runs once, at most `MAX_PLY` deep.
+    let near_end = "turn: first\nG G G R29 / G G G R28";
+    let (code, res) = request(
+        addr,
+        "POST",
+        "/evaluate",
+        Some(json!({ "position": near_end, "ply": 50, "rollout": 1000000 })),
+    );
+    assert_eq!(code, 200);
+    assert_eq!(res["ply"], MAX_PLY);
+    server.stop();
+}
@@ -0,0 +1,169 @@
+#![cfg(feature = "ws")]
+
+use std::{net::TcpStream, thread, time::Duration};
+
+use kariuchi_solver::{game::State, ws::GameServer};
+use serde_json::{json, Value};
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+type Client = WebSocket<MaybeTlsStream<TcpStream>>;
+
+// Every room throws from the same seeded tape, whose first throw is a 3.
+fn start() -> String {
+    let server = GameServer::bind("127.0.0.1:0").unwrap().with_seed(1);
+    let url = format!("ws://{}/", server.addr());
+    thread::spawn(move || server.run());
+    url
+}
+
+fn connect(url: &str) -> Client {
+    tungstenite::connect(url).unwrap().0
+}
+
+fn send(c: &mut Client, v: Value) {
+    c.send(Message::Text(v.to_string())).unwrap();
+}
+
+fn recv(c: &mut Client) -> Value {
+    loop {
+        if let Message::Text(t) = c.read().unwrap() {
+            return serde_json::from_str(&t).unwrap();
+        }
+    }
+}
+
+// Skips messages until one of the given type arrives.
+fn recv_type(c: &mut Client, ty: &str) -> Value {
+    loop {
+        let v = recv(c);
+        if v["type"] == ty {
+            return v;
+        }
+    }
+}
+
+#[test]
+fn two_players() {
+    let url = start();
+    let mut a = connect(&url);
+    let mut b = connect(&url);
+    send(&mut a, json!({ "type": "join", "room": "r" }));
+    assert_eq!(recv_type(&mut a, "joined")["seat"], "first");
+    send(&mut b, json!({ "type": "join", "room": "r" }));
+    assert_eq!(recv_type(&mut b, "joined")["seat"], "second");
+    let state = recv_type(&mut b, "state");
+    assert_eq!(state["turn"], "first");
+    assert_eq!(state["sticks"], 3);
+    assert_eq!(state["moves"].as_array().unwrap().len(), 1);
+
+    send(&mut b, json!({ "type": "move", "index": 0 }));
+    assert_eq!(recv_type(&mut b, "error")["message"], "not your turn");
+    send(&mut a, json!({ "type": "move", "index": 3 }));
+    assert_eq!(recv_type(&mut a, "error")["message"], "no move 3");
+
+    send(&mut a, json!({ "type": "move", "index": 0 }));
+    let state = recv_type(&mut b, "state");
+    assert_eq!(state["last"], "S -> R3");
+    let position: State = state["position"].as_str().unwrap().parse().unwrap();
+    assert_ne!(position, State::new());
+    assert!(!state["diff"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn spectator() {
+    let url = start();
+    let mut clients: Vec<Client> = (0..3).map(|_| connect(&url)).collect();
+    let mut seats = vec![];
+    for c in clients.iter_mut() {
+        send(c, json!({ "type": "join", "room": "watch" }));
+        seats.push(recv_type(c, "joined")["seat"].clone());
+    }
+    assert_eq!(seats, vec!["first", "second", "spectator"]);
+    send(&mut clients[2], json!({ "type": "move", "index": 0 }));
+    assert_eq!(
+        recv_type(&mut clients[2], "error")["message"],
+        "not your turn"
+    );
+}
+
+#[test]
+fn against_engine_to_the_end() {
+    let url = start();
+    let mut a = connect(&url);
+    send(
+        &mut a,
+        json!({
+            "type": "join",
+            "room": "engine",
+            "engine": "greedy",
+            "hint": { "ply": 0, "rollout": 1 },
+        }),
+    );
+    assert_eq!(recv_type(&mut a, "joined")["seat"], "first");
+    loop {
+        let state = recv_type(&mut a, "state");
+        if !state["winner"].is_null() {
+            break;
+        }
+        if state["turn"] == "first" {
+            assert!(state["hint"]["index"].is_u64());
+            send(&mut a, json!({ "type": "move", "index": 0 }));
+        } else {
+            assert!(state["hint"].is_null());
+        }
+    }
+}
+
+#[test]
+fn bad_messages() {
+    let url = start();
+    let mut a = connect(&url);
+    send(&mut a, json!({ "type": "move", "index": 0 }));
+    assert_eq!(recv_type(&mut a, "error")["message"], "join a room first");
+    send(&mut a, json!({ "type": "dance" }));
+    recv_type(&mut a, "error");
+    send(
+        &mut a,
+        json!({ "type": "join", "room": "x", "engine": "nobody" }),
+    );
+    recv_type(&mut a, "error");
+    send(
+        &mut a,
+        json!({ "type": "join", "room": "y", "engine": "human" }),
+    );
+    assert_eq!(
+        recv_type(&mut a, "error")["message"],
+        "`human` is not an engine"
+    );
+    send(
+        &mut a,
+        json!({ "type": "join", "room": "z", "engine": "expectimax:ply=9" }),
+    );
+    assert_eq!(
+        recv_type(&mut a, "error")["message"],
+        "engine `ply` is at most 3"
+    );
+}
+
+#[test]
+fn empty_rooms_go() {
+    let url = start();
+    let mut a = connect(&url);
+    send(&mut a, json!({ "type": "join", "room": "gone" }));
+    recv_type(&mut a, "joined");
+    recv_type(&mut a, "state");
+    send(&mut a, json!({ "type": "move", "index": 0 }));
+    let state = recv_type(&mut a, "state");
+    assert_ne!(state["position"], State::new().to_string());
+    a.close(None).unwrap();
+    while a.read().is_ok() {}
+    // The server leaves the room just after answering the close.
+    thread::sleep(Duration::from_millis(100));
+
+    // The room went with its last client, so joining again starts a new game.
+    let mut b = connect(&url);
+    send(&mut b, json!({ "type": "join", "room": "gone" }));
+    assert_eq!(recv_type(&mut b, "joined")["seat"], "first");
+    let state = recv_type(&mut b, "state");
+    assert!(state["last"].is_null());
+}